@@ -0,0 +1,465 @@
+//! Lossless concrete syntax tree.
+//!
+//! Every byte of the source belongs either to a token or to the trivia in front of it,
+//! so printing the tree gives back the input unchanged. The AST is derived from it,
+//! one sentence at a time.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    parser::{ParseError, Sentence, State},
+    span::{Position, Span},
+    symbol::{Interner, Symbol},
+    token::{self, Reserved, Token, TokenKind},
+    tokenize::{LexError, LexerConfig},
+    transliterate::Transliteration,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TriviaKind {
+    Whitespace,
+    // `#` up to, but not including, the end of the line
+    LineComment,
+    // `#{` up to and including `}#`
+    BlockComment,
+}
+
+/// Source text that is not part of any token.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: Symbol,
+    pub span: Span,
+}
+
+/// A token together with its exact spelling and the trivia preceding it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CstToken {
+    pub leading_trivia: Vec<Trivia>,
+    pub text: Symbol,
+    pub token: Token,
+}
+
+/// The tokens of one sentence, up to and including its `.`.
+/// Only the last sentence of a file may lack the `.`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CstSentence {
+    pub tokens: Vec<CstToken>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Cst {
+    pub sentences: Vec<CstSentence>,
+    // trivia after the last token
+    pub trailing_trivia: Vec<Trivia>,
+    // how the source was read, so that an edited source is read the same way
+    config: LexerConfig,
+    transliteration: Option<Transliteration>,
+}
+
+impl Trivia {
+    #[must_use]
+    pub const fn is_comment(&self) -> bool {
+        matches!(
+            self.kind,
+            TriviaKind::LineComment | TriviaKind::BlockComment
+        )
+    }
+}
+
+// The trivia between two tokens, i.e. the source text from `start` to `end`.
+// The lexer has already checked it consists of whitespace and well-formed comments only.
+fn trivia(input: &str, start: Position, end: Position, interner: &mut Interner) -> Vec<Trivia> {
+    let mut trivia = vec![];
+    let mut pos = start;
+    let mut rest = &input[start.offset..end.offset];
+    while !rest.is_empty() {
+        let (kind, len) = if rest.starts_with("#{") {
+            let len = rest.find("}#").map_or(rest.len(), |i| i + 2);
+            (TriviaKind::BlockComment, len)
+        } else if rest.starts_with('#') {
+            (
+                TriviaKind::LineComment,
+                rest.find('\n').unwrap_or(rest.len()),
+            )
+        } else {
+            (TriviaKind::Whitespace, rest.find('#').unwrap_or(rest.len()))
+        };
+        let (text, remaining) = rest.split_at(len);
+        let next_pos = pos.advance(text);
+        trivia.push(Trivia {
+            kind,
+            text: interner.intern(text),
+            span: Span::new(pos, next_pos),
+        });
+        pos = next_pos;
+        rest = remaining;
+    }
+    trivia
+}
+
+impl Cst {
+    /// Builds the tree of a whole `.jmk4` source.
+    ///
+    /// # Errors
+    /// Returns the `LexError` if the source cannot be tokenized.
+    pub fn parse(input: &str) -> Result<Self, LexError> {
+        Self::parse_with(input, &LexerConfig::default())
+    }
+
+    /// Same as `parse`, with the character set of `config`.
+    ///
+    /// # Errors
+    /// Returns the `LexError` if the source cannot be tokenized.
+    pub fn parse_with(input: &str, config: &LexerConfig) -> Result<Self, LexError> {
+        let tokens = token::tokenize_with(input, config)?;
+        Ok(Self::from_tokens(input, tokens, config.clone(), None))
+    }
+
+    /// Same as `parse_with`, reading `input` through `transliteration`.
+    /// The text of the tokens and the trivia is that of `input`; their kinds are romanised.
+    ///
+    /// # Errors
+    /// Returns the `LexError` if the source cannot be tokenized.
+    pub fn parse_transliterated(
+        input: &str,
+        transliteration: &Transliteration,
+        config: &LexerConfig,
+    ) -> Result<Self, LexError> {
+        let tokens = token::tokenize_transliterated(input, transliteration, config)?;
+        Ok(Self::from_tokens(
+            input,
+            tokens,
+            config.clone(),
+            Some(transliteration.clone()),
+        ))
+    }
+
+    // `tokens` must be those of `input`, in order
+    fn from_tokens(
+        input: &str,
+        tokens: Vec<Token>,
+        config: LexerConfig,
+        transliteration: Option<Transliteration>,
+    ) -> Self {
+        let mut sentences = vec![];
+        let mut tokens_of_sentence = vec![];
+        let mut pos = Position::default();
+        // the text of the tree, shared between equal spellings and freed with it
+        let mut interner = Interner::new();
+        for token in tokens {
+            let leading_trivia = trivia(input, pos, token.span.start, &mut interner);
+            let text = interner.intern(&input[token.span.range()]);
+            pos = token.span.end;
+            // A sentence never contains `.`, so it ends at the first one
+            let ends_sentence = token.kind == TokenKind::Reserved(Reserved::PunctuationPeriod);
+            tokens_of_sentence.push(CstToken {
+                leading_trivia,
+                text,
+                token,
+            });
+            if ends_sentence {
+                sentences.push(CstSentence {
+                    tokens: std::mem::take(&mut tokens_of_sentence),
+                });
+            }
+        }
+        if !tokens_of_sentence.is_empty() {
+            sentences.push(CstSentence {
+                tokens: tokens_of_sentence,
+            });
+        }
+        let end = pos.advance(&input[pos.offset..]);
+        Self {
+            sentences,
+            trailing_trivia: trivia(input, pos, end, &mut interner),
+            config,
+            transliteration,
+        }
+    }
+
+    /// The configuration the source was lexed with.
+    #[must_use]
+    pub const fn config(&self) -> &LexerConfig {
+        &self.config
+    }
+
+    /// Every comment in the source, in source order.
+    pub fn comments(&self) -> impl Iterator<Item = &Trivia> {
+        self.sentences
+            .iter()
+            .flat_map(|sentence| &sentence.tokens)
+            .flat_map(|tok| &tok.leading_trivia)
+            .chain(&self.trailing_trivia)
+            .filter(|trivia| trivia.is_comment())
+    }
+
+    /// Derives the AST of every sentence.
+    ///
+    /// # Errors
+    /// Returns the first `ParseError` encountered.
+    pub fn to_ast(&self) -> Result<Vec<Sentence>, ParseError> {
+        self.sentences.iter().map(CstSentence::to_ast).collect()
+    }
+
+    /// Derives the AST of every sentence that can be parsed, together with the errors of those
+    /// that cannot, in source order.
+    #[must_use]
+    pub fn to_ast_with_recovery(&self) -> (Vec<Sentence>, Vec<ParseError>) {
+        let mut sentences = vec![];
+        let mut errors = vec![];
+        for sentence in &self.sentences {
+            match sentence.to_ast() {
+                Ok(sentence) => sentences.push(sentence),
+                Err(err) => errors.push(err),
+            }
+        }
+        (sentences, errors)
+    }
+
+    /// Replaces the sentence at `index` with `text`, leaving every other byte of the source as
+    /// it is. The trivia in front of the old sentence is kept, and the source is read again
+    /// with the configuration and transliteration the tree was built with.
+    ///
+    /// # Errors
+    /// Returns the `LexError` if the resulting source cannot be tokenized; the tree is then
+    /// left unchanged.
+    pub fn replace_sentence(&mut self, index: usize, text: &str) -> Result<(), LexError> {
+        let mut source = String::new();
+        for (i, sentence) in self.sentences.iter().enumerate() {
+            if i == index {
+                for trivia in sentence.leading_trivia() {
+                    source.push_str(trivia.text.as_str());
+                }
+                source.push_str(text);
+            } else {
+                source.push_str(&sentence.to_string());
+            }
+        }
+        for trivia in &self.trailing_trivia {
+            source.push_str(trivia.text.as_str());
+        }
+        *self = match &self.transliteration {
+            Some(transliteration) => {
+                Self::parse_transliterated(&source, transliteration, &self.config)?
+            }
+            None => Self::parse_with(&source, &self.config)?,
+        };
+        Ok(())
+    }
+}
+
+impl CstSentence {
+    /// The trivia in front of the first token of the sentence.
+    #[must_use]
+    pub fn leading_trivia(&self) -> &[Trivia] {
+        self.tokens
+            .first()
+            .map_or(&[], |tok| tok.leading_trivia.as_slice())
+    }
+
+    /// Derives the AST of the sentence.
+    ///
+    /// # Errors
+    /// Returns the `ParseError` if the sentence is malformed.
+    pub fn to_ast(&self) -> Result<Sentence, ParseError> {
+        State::new(&self.tokens).parse_terminated_sentence()
+    }
+}
+
+impl AsRef<Token> for CstToken {
+    fn as_ref(&self) -> &Token {
+        &self.token
+    }
+}
+
+impl Display for CstToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for trivia in &self.leading_trivia {
+            f.write_str(trivia.text.as_str())?;
+        }
+        f.write_str(self.text.as_str())
+    }
+}
+
+impl Display for CstSentence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for tok in &self.tokens {
+            write!(f, "{tok}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Cst {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for sentence in &self.sentences {
+            write!(f, "{sentence}")?;
+        }
+        for trivia in &self.trailing_trivia {
+            f.write_str(trivia.text.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Rule, span::WithoutSpans};
+
+    #[test]
+    fn selsurle_round_trip() {
+        let source = include_str!("../selsurle.jmk4");
+        let cst = Cst::parse(source).unwrap();
+        assert_eq!(cst.to_string().as_bytes(), source.as_bytes());
+        assert_eq!(cst.sentences.len(), 8);
+        assert_eq!(
+            cst.to_ast().unwrap().without_spans(),
+            crate::selsurle_sentences()
+        );
+    }
+
+    #[test]
+    fn string_literal_spacing() {
+        let sources = [
+            "is jerldir'd xakant <selsurle>'c.",
+            "is jerldir'd xakant<selsurle>'c.",
+            "is jerldir'd xakant<selsurle> 'c.",
+            "is jerldir'd xakant <selsurle> 'c.",
+        ];
+        let csts = sources.map(|source| Cst::parse(source).unwrap());
+        for (cst, source) in csts.iter().zip(sources) {
+            assert_eq!(cst.to_string(), source);
+            assert_eq!(
+                cst.to_ast().unwrap().without_spans(),
+                csts[0].to_ast().unwrap().without_spans()
+            );
+        }
+        assert_ne!(csts[0], csts[1]);
+        assert_ne!(csts[1], csts[2]);
+        assert_ne!(csts[2], csts[3]);
+    }
+
+    #[test]
+    fn trivia() {
+        let cst = Cst::parse("  selsurle\tes iu .\n\n").unwrap();
+        let tokens = &cst.sentences[0].tokens;
+        assert_eq!(tokens[0].leading_trivia[0].text, "  ");
+        assert_eq!(tokens[0].leading_trivia[0].span.range(), 0..2);
+        assert_eq!(tokens[1].leading_trivia[0].text, "\t");
+        assert_eq!(tokens[3].leading_trivia[0].text, " ");
+        assert_eq!(tokens[3].text, ".");
+        assert_eq!(tokens[2].leading_trivia.len(), 1);
+        assert_eq!(cst.trailing_trivia[0].text, "\n\n");
+        assert_eq!(cst.trailing_trivia[0].span.end.line, 3);
+    }
+
+    #[test]
+    fn unterminated_last_sentence() {
+        let cst = Cst::parse("selsurle es iu.\nlus jmk4'd deln").unwrap();
+        assert_eq!(cst.sentences.len(), 2);
+        assert_eq!(cst.sentences[1].to_string(), "\nlus jmk4'd deln");
+        assert!(cst.trailing_trivia.is_empty());
+        assert!(matches!(
+            cst.to_ast().unwrap_err(),
+            ParseError::EndOfFile {
+                rule: Rule::Import,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn replace_sentence() {
+        let source =
+            "selsurle   es iu.\n\n  is jerldir'd xakant<selsurle>'c.\nselsurle es  iulo .\n";
+        let mut cst = Cst::parse(source).unwrap();
+        cst.replace_sentence(1, "is selsurle iu'c.").unwrap();
+        assert_eq!(
+            cst.to_string(),
+            "selsurle   es iu.\n\n  is selsurle iu'c.\nselsurle es  iulo .\n"
+        );
+        assert!(cst.replace_sentence(0, "Selsurle es iu.").is_err());
+        assert_eq!(
+            cst.to_string(),
+            "selsurle   es iu.\n\n  is selsurle iu'c.\nselsurle es  iulo .\n"
+        );
+    }
+
+    #[test]
+    fn replace_sentence_native_script() {
+        let config = LexerConfig {
+            period: '。',
+            ..LexerConfig::unicode_xid()
+        };
+        let mut cst = Cst::parse_with("選択 es iu。\n選択 es iulo。\n", &config).unwrap();
+        cst.replace_sentence(1, "is 選択 iu'c。").unwrap();
+        assert_eq!(cst.to_string(), "選択 es iu。\nis 選択 iu'c。\n");
+        assert_eq!(cst.sentences.len(), 2);
+        assert_eq!(cst.to_ast().unwrap().len(), 2);
+
+        let mut cst = Cst::parse_transliterated(
+            "Bap\\ es iu.",
+            &Transliteration::ascii(),
+            &LexerConfig::default(),
+        )
+        .unwrap();
+        cst.replace_sentence(0, "Bap\\ es iulo.").unwrap();
+        assert_eq!(
+            cst.to_ast().unwrap().without_spans(),
+            crate::parse_program("βaφ es iulo.")
+                .unwrap()
+                .without_spans()
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let source = "# selsurle\nselsurle es #{ iulo }# iu. # iu\n";
+        let cst = Cst::parse(source).unwrap();
+        assert_eq!(cst.to_string(), source);
+        let comments = cst
+            .comments()
+            .map(|trivia| (trivia.kind, trivia.text.as_str(), trivia.span.range()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            comments,
+            vec![
+                (TriviaKind::LineComment, "# selsurle", 0..10),
+                (TriviaKind::BlockComment, "#{ iulo }#", 23..33),
+                (TriviaKind::LineComment, "# iu", 38..42),
+            ]
+        );
+        assert_eq!(
+            cst.sentences[0].tokens[0]
+                .leading_trivia
+                .iter()
+                .map(|trivia| trivia.kind)
+                .collect::<Vec<_>>(),
+            vec![TriviaKind::LineComment, TriviaKind::Whitespace]
+        );
+        assert_eq!(
+            cst.to_ast().unwrap().without_spans(),
+            crate::parse_program("selsurle es iu.")
+                .unwrap()
+                .without_spans()
+        );
+    }
+
+    #[test]
+    fn transliterated() {
+        let source = "selsurle es #{ Z }#\n  Bap\\ .";
+        let cst =
+            Cst::parse_transliterated(source, &Transliteration::ascii(), &LexerConfig::default())
+                .unwrap();
+        assert_eq!(cst.to_string(), source);
+        assert_eq!(cst.sentences[0].tokens[2].text, "Bap\\");
+        assert_eq!(
+            cst.to_ast().unwrap().without_spans(),
+            crate::parse_program("selsurle es βaφ.")
+                .unwrap()
+                .without_spans()
+        );
+    }
+}
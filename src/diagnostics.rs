@@ -0,0 +1,453 @@
+use std::fmt::Write as _;
+
+use crate::{
+    loader::ImportError, messages::Locale, parser::ParseError, predicate::PredicateError,
+    resolve::ResolveError, signature::SignatureError, span::Span, tokenize::LexError,
+    types::TypeError, Error,
+};
+
+/// An error message together with where it happened, ready to be rendered against the source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub locale: Locale,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub label: String,
+    pub expected: Option<String>,
+    pub note: Option<String>,
+}
+
+struct Style {
+    error: &'static str,
+    gutter: &'static str,
+    caret: &'static str,
+    message: &'static str,
+    reset: &'static str,
+}
+
+const PLAIN: Style = Style {
+    error: "",
+    gutter: "",
+    caret: "",
+    message: "",
+    reset: "",
+};
+
+const ANSI: Style = Style {
+    error: "\x1b[1;31m",
+    gutter: "\x1b[1;34m",
+    caret: "\x1b[1;31m",
+    message: "\x1b[1m",
+    reset: "\x1b[0m",
+};
+
+impl Diagnostic {
+    #[must_use]
+    pub fn new(err: &Error, locale: Locale) -> Self {
+        match err {
+            Error::Lex(err) => Self::from_lex_error(err, locale),
+            Error::Parse(err) => Self::from_parse_error(err, locale),
+            Error::Resolve(err) => Self::from_resolve_error(err, locale),
+            Error::Import(err) => Self::from_import_error(err, locale),
+            Error::Signature(err) => Self::from_signature_error(err, locale),
+            Error::Type(err) => Self::from_type_error(err, locale),
+            Error::Predicate(err) => Self::from_predicate_error(err, locale),
+        }
+    }
+
+    #[must_use]
+    pub fn from_predicate_error(err: &PredicateError, locale: Locale) -> Self {
+        Self {
+            locale,
+            code: err.code(),
+            message: locale.predicate_error_message(&err.kind),
+            span: err.span,
+            label: locale.predicate_error_label(&err.kind).to_string(),
+            expected: None,
+            note: locale.predicate_error_note(&err.kind),
+        }
+    }
+
+    #[must_use]
+    pub fn from_type_error(err: &TypeError, locale: Locale) -> Self {
+        Self {
+            locale,
+            code: err.code(),
+            message: locale.type_error_message(&err.kind),
+            span: err.span,
+            label: locale.type_error_label(&err.kind).to_string(),
+            expected: None,
+            note: None,
+        }
+    }
+
+    #[must_use]
+    pub fn from_signature_error(err: &SignatureError, locale: Locale) -> Self {
+        Self {
+            locale,
+            code: err.code(),
+            message: locale.signature_error_message(&err.kind),
+            span: err.span,
+            label: locale.signature_error_label(&err.kind).to_string(),
+            expected: None,
+            note: None,
+        }
+    }
+
+    #[must_use]
+    pub fn from_import_error(err: &ImportError, locale: Locale) -> Self {
+        Self {
+            locale,
+            code: err.code(),
+            message: locale.import_error_message(&err.kind),
+            span: err.span,
+            label: locale.import_error_label().to_string(),
+            expected: None,
+            note: Some(locale.import_error_note(&err.kind)),
+        }
+    }
+
+    #[must_use]
+    pub fn from_resolve_error(err: &ResolveError, locale: Locale) -> Self {
+        Self {
+            locale,
+            code: err.code(),
+            message: locale.resolve_error_message(&err.kind),
+            span: err.span,
+            label: locale.resolve_error_label(&err.kind),
+            expected: None,
+            note: Some(locale.resolve_error_note(&err.kind)),
+        }
+    }
+
+    #[must_use]
+    pub fn from_lex_error(err: &LexError, locale: Locale) -> Self {
+        Self {
+            locale,
+            code: err.code(),
+            message: locale.lex_error_message(&err.kind),
+            span: err.span,
+            label: locale.lex_error_label(&err.kind),
+            expected: None,
+            note: locale.lex_error_note(&err.kind),
+        }
+    }
+
+    #[must_use]
+    pub fn from_parse_error(err: &ParseError, locale: Locale) -> Self {
+        match err {
+            ParseError::EndOfFile { rule, span, .. } => Self {
+                locale,
+                code: err.code(),
+                message: locale.end_of_file(),
+                span: *span,
+                label: locale.end_of_file_label(*rule),
+                expected: Some(err.expected_message(locale)),
+                note: Some(locale.end_of_file_note()),
+            },
+            ParseError::UnexpectedToken { rule, actual, .. } => Self {
+                locale,
+                code: err.code(),
+                message: locale.unexpected_token(&actual.kind),
+                span: actual.span,
+                label: locale.unexpected_token_label(*rule),
+                expected: Some(err.expected_message(locale)),
+                note: None,
+            },
+        }
+    }
+}
+
+impl From<&LexError> for Diagnostic {
+    fn from(err: &LexError) -> Self {
+        Self::from_lex_error(err, Locale::default())
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(err: &ParseError) -> Self {
+        Self::from_parse_error(err, Locale::default())
+    }
+}
+
+impl From<&ResolveError> for Diagnostic {
+    fn from(err: &ResolveError) -> Self {
+        Self::from_resolve_error(err, Locale::default())
+    }
+}
+
+impl From<&ImportError> for Diagnostic {
+    fn from(err: &ImportError) -> Self {
+        Self::from_import_error(err, Locale::default())
+    }
+}
+
+impl From<&SignatureError> for Diagnostic {
+    fn from(err: &SignatureError) -> Self {
+        Self::from_signature_error(err, Locale::default())
+    }
+}
+
+impl From<&TypeError> for Diagnostic {
+    fn from(err: &TypeError) -> Self {
+        Self::from_type_error(err, Locale::default())
+    }
+}
+
+impl From<&PredicateError> for Diagnostic {
+    fn from(err: &PredicateError) -> Self {
+        Self::from_predicate_error(err, Locale::default())
+    }
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(err: &Error) -> Self {
+        Self::new(err, Locale::default())
+    }
+}
+
+impl Diagnostic {
+    /// Renders the diagnostic as plain text, quoting the offending line of `source`.
+    #[must_use]
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        self.render_with(file_name, source, &PLAIN)
+    }
+
+    /// Same as `render`, but colored with ANSI escape sequences for terminals.
+    #[must_use]
+    pub fn render_ansi(&self, file_name: &str, source: &str) -> String {
+        self.render_with(file_name, source, &ANSI)
+    }
+
+    fn render_with(&self, file_name: &str, source: &str, style: &Style) -> String {
+        let Style {
+            error,
+            gutter,
+            caret,
+            message,
+            reset,
+        } = style;
+        let start = self.span.start;
+        let line_text = source.lines().nth(start.line - 1).unwrap_or("");
+        let line_number = start.line.to_string();
+        let pad = " ".repeat(line_number.len());
+
+        // A span running over several lines is underlined up to the end of its first line
+        let caret_len = if self.span.end.line == start.line {
+            self.span.end.column - start.column
+        } else {
+            line_text.chars().count().saturating_sub(start.column - 1)
+        }
+        .max(1);
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{error}{}[{}]{reset}{message}: {}{reset}",
+            self.locale.error_heading(),
+            self.code,
+            self.message
+        );
+        let _ = writeln!(
+            out,
+            "{pad}{gutter}-->{reset} {file_name}:{}:{}",
+            start.line, start.column
+        );
+        let _ = writeln!(out, "{pad} {gutter}|{reset}");
+        let _ = writeln!(out, "{gutter}{line_number} |{reset} {line_text}");
+        let _ = writeln!(
+            out,
+            "{pad} {gutter}|{reset} {}{caret}{} {}{reset}",
+            " ".repeat(start.column - 1),
+            "^".repeat(caret_len),
+            self.label
+        );
+        if let Some(expected) = &self.expected {
+            let heading = self.locale.expected_heading();
+            let _ = writeln!(out, "{pad} {gutter}={reset} {heading}: {expected}");
+        }
+        if let Some(note) = &self.note {
+            let heading = self.locale.note_heading();
+            let _ = writeln!(out, "{pad} {gutter}={reset} {heading}: {note}");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    fn diagnostic(source: &str) -> Diagnostic {
+        (&parse_program(source).unwrap_err()).into()
+    }
+
+    #[test]
+    fn unexpected_token() {
+        let source = "selsurle es iu.\nselsurle es .\n";
+        assert_eq!(
+            diagnostic(source).render("selsurle.jmk4", source),
+            "エラー[E0001]: 予期しないトークン `.` です
+ --> selsurle.jmk4:2:13
+  |
+2 | selsurle es .
+  |             ^ `primary_noun` の途中にこのトークンは置けません
+  = 期待されるもの: （識別子） / （文字列リテラル） / （数値）
+"
+        );
+    }
+
+    #[test]
+    fn unterminated_string_literal() {
+        let source = "is jerldir'd xakant <selsurle'c.";
+        assert_eq!(
+            diagnostic(source).render("a.jmk4", source),
+            "エラー[E0103]: 文字列リテラルが閉じられていません
+ --> a.jmk4:1:21
+  |
+1 | is jerldir'd xakant <selsurle'c.
+  |                     ^^^^^^^^^^^^ ここから始まる文字列リテラル
+  = 注: 文字列リテラルは `>` で閉じる必要があります
+"
+        );
+    }
+
+    #[test]
+    fn end_of_file() {
+        let source = "lus jmk4'd deln";
+        assert_eq!(
+            diagnostic(source).render("a.jmk4", source),
+            "エラー[E0002]: 予期しないファイル終端です
+ --> a.jmk4:1:16
+  |
+1 | lus jmk4'd deln
+  |                ^ `import` の途中で入力が終わっています
+  = 期待されるもの: 'd / . / ad / adit
+  = 注: 文は `.` で終わる必要があります
+"
+        );
+    }
+
+    #[test]
+    fn ansi() {
+        let source = "selsurle es Iu.";
+        assert_eq!(
+            diagnostic(source).render_ansi("a.jmk4", source),
+            "\x1b[1;31mエラー[E0101]\x1b[0m\x1b[1m: 使えない文字 `I` です\x1b[0m
+ \x1b[1;34m-->\x1b[0m a.jmk4:1:13
+  \x1b[1;34m|\x1b[0m
+\x1b[1;34m1 |\x1b[0m selsurle es Iu.
+  \x1b[1;34m|\x1b[0m             \x1b[1;31m^ この文字\x1b[0m
+  \x1b[1;34m=\x1b[0m 注: `<` と `>` で囲んだ文字列リテラルの中ではどの文字でも使えます
+"
+        );
+    }
+
+    #[test]
+    fn english() {
+        let source = "lus jmk4'd deln";
+        let err = parse_program(source).unwrap_err();
+        assert_eq!(
+            Diagnostic::new(&err, Locale::English).render("a.jmk4", source),
+            "error[E0002]: unexpected end of file
+ --> a.jmk4:1:16
+  |
+1 | lus jmk4'd deln
+  |                ^ the input ends in the middle of `import`
+  = expected: 'd / . / ad / adit
+  = note: a sentence must end with `.`
+"
+        );
+        let source = "selsurle es .";
+        let err = parse_program(source).unwrap_err();
+        assert_eq!(
+            Diagnostic::new(&err, Locale::English).render("a.jmk4", source),
+            "error[E0001]: unexpected token `.`
+ --> a.jmk4:1:13
+  |
+1 | selsurle es .
+  |             ^ this token cannot appear in `primary_noun`
+  = expected: an identifier / a string literal / a number
+"
+        );
+    }
+
+    #[test]
+    fn resolve_error() {
+        let source = "selsurle es iu.
+selsurle es deln.
+";
+        let errors = crate::resolve_program(source).unwrap_err();
+        let rendered = errors
+            .iter()
+            .map(|err| Diagnostic::from(err).render("a.jmk4", source))
+            .collect::<String>();
+        assert_eq!(
+            rendered,
+            "エラー[E0202]: `selsurle` が二度定義されています
+ --> a.jmk4:2:1
+  |
+2 | selsurle es deln.
+  | ^^^^^^^^ 二度目の定義
+  = 注: 最初の定義は 1 行目です
+エラー[E0201]: `deln` は定義されていません
+ --> a.jmk4:2:13
+  |
+2 | selsurle es deln.
+  |             ^^^^ この名前
+  = 注: 名前は `lus` で取り込むか `es` で宣言する必要があります
+"
+        );
+    }
+
+    #[test]
+    fn type_error() {
+        let source = "lus jmk4'd jerldir.\nlaozia jerldir lerj <10> ad 10 el 168 ad 218.\n";
+        let errors = crate::check_program(source).unwrap_err();
+        assert_eq!(
+            Diagnostic::new(&errors[0], Locale::English).render("a.jmk4", source),
+            "error[E0501]: mismatched types: expected `number`, found `string`
+ --> a.jmk4:2:21
+  |
+2 | laozia jerldir lerj <10> ad 10 el 168 ad 218.
+  |                     ^^^^ this noun
+"
+        );
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn predicate_error() {
+        let source =
+            "nert ad ektir'st es_tydivexy-o : ektir mol.\niu es_tydivexy felx shrlo is iu iu'c.\n";
+        let errors = crate::check_program(source).unwrap_err();
+        let rendered = errors
+            .iter()
+            .map(|err| Diagnostic::from(err).render("a.jmk4", source))
+            .collect::<String>();
+        assert_eq!(
+            rendered,
+            "エラー[E0601]: 引数 `nert` が使われていません
+ --> a.jmk4:1:1
+  |
+1 | nert ad ektir'st es_tydivexy-o : ektir mol.
+  | ^^^^ この引数
+エラー[E0603]: 述語 `es_tydivexy` の引数は 2 個ですが 1 個あります
+ --> a.jmk4:2:1
+  |
+2 | iu es_tydivexy felx shrlo is iu iu'c.
+  | ^^^^^^^^^^^^^^ この条件
+  = 注: 引数は主語と格の付いた名詞です
+"
+        );
+        // the sample calls `es_tydivexy` with one argument where it declares two
+        let codes = crate::check_program(include_str!("../selsurle.jmk4"))
+            .unwrap_err()
+            .iter()
+            .map(Error::code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec!["E0603", "E0603"]);
+    }
+}
@@ -0,0 +1,534 @@
+//! Prints the AST back as JMK4 source in canonical form:
+//! suffixes such as `'d`, `'c`, `'st`, `-il`, `-o` are attached to the word,
+//! words are separated by one space, and every `melx shrlo` starts a new line.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    cst::{Cst, Trivia, TriviaKind},
+    parser::{
+        Case, Cond, CondElem, EventCond, Import, MeaElem, Module, NamedParameter, Noun,
+        NounsWithCase, ParseError, PrimaryNoun, Procedure, Sentence, Verb,
+    },
+    symbol::Symbol,
+    tokenize::LexerConfig,
+};
+
+/// Formats a whole program, one sentence per paragraph.
+#[must_use]
+pub fn format_program(sentences: &[Sentence]) -> String {
+    format_program_with(sentences, &LexerConfig::default())
+}
+
+/// Formats a whole program like `format_program`, with the punctuation and string literal
+/// delimiters of `config`.
+#[must_use]
+pub fn format_program_with(sentences: &[Sentence], config: &LexerConfig) -> String {
+    sentences
+        .iter()
+        .map(|sentence| format!("{}{}\n", configured(sentence, config), config.period))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Formats a whole program like `format_program`, keeping its comments, with the punctuation
+/// and string literal delimiters the CST was lexed with.
+///
+/// A comment on the line where a sentence ends stays right after that sentence;
+/// any other comment is put on a line of its own in front of the sentence it appears in.
+///
+/// # Errors
+/// Returns the first `ParseError` encountered.
+pub fn format_cst(cst: &Cst) -> Result<String, ParseError> {
+    let mut paragraphs: Vec<String> = vec![];
+    for sentence in &cst.sentences {
+        let ast = sentence.to_ast()?;
+        let mut leading = sentence.leading_trivia();
+        if let Some(previous) = paragraphs.last_mut() {
+            let (same_line, rest) = split_at_newline(leading);
+            append_same_line_comments(previous, same_line);
+            leading = rest;
+        }
+        let inner = sentence
+            .tokens
+            .iter()
+            .skip(1)
+            .flat_map(|tok| &tok.leading_trivia);
+        let mut paragraph = String::new();
+        for comment in leading.iter().chain(inner).filter(|t| t.is_comment()) {
+            paragraph.push_str(comment.text.as_str());
+            paragraph.push('\n');
+        }
+        paragraph.push_str(&configured(&ast, cst.config()).to_string());
+        paragraph.push(cst.config().period);
+        paragraphs.push(paragraph);
+    }
+
+    let mut trailing = cst.trailing_trivia.as_slice();
+    if let Some(previous) = paragraphs.last_mut() {
+        let (same_line, rest) = split_at_newline(trailing);
+        append_same_line_comments(previous, same_line);
+        trailing = rest;
+    }
+    let comments = trailing
+        .iter()
+        .filter(|t| t.is_comment())
+        .map(|t| t.text.as_str())
+        .collect::<Vec<_>>();
+    if !comments.is_empty() {
+        paragraphs.push(comments.join("\n"));
+    }
+
+    Ok(paragraphs
+        .iter()
+        .map(|paragraph| format!("{paragraph}\n"))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+// Splits trivia into what precedes the first line break and the rest.
+fn split_at_newline(trivia: &[Trivia]) -> (&[Trivia], &[Trivia]) {
+    let i = trivia
+        .iter()
+        .position(|t| t.kind == TriviaKind::Whitespace && t.text.as_str().contains('\n'))
+        .unwrap_or(trivia.len());
+    trivia.split_at(i)
+}
+
+fn append_same_line_comments(paragraph: &mut String, trivia: &[Trivia]) {
+    for comment in trivia.iter().filter(|t| t.is_comment()) {
+        paragraph.push(' ');
+        paragraph.push_str(comment.text.as_str());
+    }
+}
+
+// Writes a node as source, with the punctuation and string literal delimiters of `config`
+trait Format {
+    fn format(&self, f: &mut Formatter<'_>, config: &LexerConfig) -> fmt::Result;
+}
+
+// `node` displayed under `config`
+struct Configured<'a, T>(&'a T, &'a LexerConfig);
+
+impl<T: Format> Display for Configured<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.0.format(f, self.1)
+    }
+}
+
+const fn configured<'a, T>(node: &'a T, config: &'a LexerConfig) -> Configured<'a, T> {
+    Configured(node, config)
+}
+
+impl Format for Symbol {
+    fn format(&self, f: &mut Formatter<'_>, _: &LexerConfig) -> fmt::Result {
+        f.write_str(self)
+    }
+}
+
+impl Format for PrimaryNoun {
+    fn format(&self, f: &mut Formatter<'_>, config: &LexerConfig) -> fmt::Result {
+        match self {
+            // the literal keeps the delimiters it was written with
+            Self::StringLiteral { literal, .. } => {
+                let mut contents = literal.chars();
+                contents.next();
+                contents.next_back();
+                write!(
+                    f,
+                    "{}{}{}",
+                    config.string_literal_open,
+                    contents.as_str(),
+                    config.string_literal_close
+                )
+            }
+            Self::Ident { ident, .. } => f.write_str(ident),
+            Self::Number { number, .. } => write!(f, "{number}"),
+        }
+    }
+}
+
+impl Format for Verb {
+    fn format(&self, f: &mut Formatter<'_>, _: &LexerConfig) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Format for Module {
+    fn format(&self, f: &mut Formatter<'_>, _: &LexerConfig) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// noun = (primary_noun "'d")* primary_noun
+impl Format for Noun {
+    fn format(&self, f: &mut Formatter<'_>, config: &LexerConfig) -> fmt::Result {
+        for modifier in &self.modifier {
+            write!(f, "{}'d ", configured(modifier, config))?;
+        }
+        self.head.format(f, config)
+    }
+}
+
+// noun_list = noun | noun "ad" noun | noun "adit" noun ("," noun)+
+fn write_list<T: Format>(f: &mut Formatter<'_>, items: &[T], config: &LexerConfig) -> fmt::Result {
+    match items {
+        [] => Ok(()),
+        [only] => only.format(f, config),
+        [first, second] => write!(
+            f,
+            "{} ad {}",
+            configured(first, config),
+            configured(second, config)
+        ),
+        [first, rest @ ..] => {
+            write!(f, "{} adit ", configured(first, config))?;
+            let rest = rest
+                .iter()
+                .map(|item| configured(item, config).to_string())
+                .collect::<Vec<_>>();
+            f.write_str(&rest.join(&format!("{} ", config.comma)))
+        }
+    }
+}
+
+// nouns_with_case = preposition noun_list | noun_list ("'c" | "'i")
+impl Format for NounsWithCase {
+    fn format(&self, f: &mut Formatter<'_>, config: &LexerConfig) -> fmt::Result {
+        match self.case {
+            Case::Preposition(p) => {
+                write!(f, "{} ", p.as_str())?;
+                write_list(f, &self.nouns, config)
+            }
+            Case::ApostropheC | Case::ApostropheI => {
+                write_list(f, &self.nouns, config)?;
+                f.write_str(self.case.as_str())
+            }
+        }
+    }
+}
+
+impl Format for NamedParameter {
+    fn format(&self, f: &mut Formatter<'_>, _: &LexerConfig) -> fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{modifier}'d ")?;
+        }
+        f.write_str(&self.head)
+    }
+}
+
+impl Format for MeaElem {
+    fn format(&self, f: &mut Formatter<'_>, config: &LexerConfig) -> fmt::Result {
+        write!(f, "{} es ", configured(&self.named_parameter, config))?;
+        write_list(f, &self.noun_list, config)
+    }
+}
+
+// procedure = verb noun nouns_with_case* mea_clause?
+impl Format for Procedure {
+    fn format(&self, f: &mut Formatter<'_>, config: &LexerConfig) -> fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            configured(&self.verb, config),
+            configured(&self.noun, config)
+        )?;
+        for nouns_with_case in &self.nouns_with_case_array {
+            write!(f, " {}", configured(nouns_with_case, config))?;
+        }
+        if let Some(mea_clause) = &self.mea_clause {
+            let mea_clause = mea_clause
+                .iter()
+                .map(|elem| configured(elem, config).to_string())
+                .collect::<Vec<_>>();
+            write!(f, " mea {}", mea_clause.join(" mal "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Format for CondElem {
+    fn format(&self, f: &mut Formatter<'_>, config: &LexerConfig) -> fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            configured(&self.noun, config),
+            configured(&self.verb, config)
+        )?;
+        for nouns_with_case in &self.nouns_with_case_array {
+            write!(f, " {}", configured(nouns_with_case, config))?;
+        }
+        Ok(())
+    }
+}
+
+// cond = noun verb nouns_with_case* ("mal" noun verb nouns_with_case*)*
+impl Format for Cond {
+    fn format(&self, f: &mut Formatter<'_>, config: &LexerConfig) -> fmt::Result {
+        let elems = self
+            .0
+            .iter()
+            .map(|elem| configured(elem, config).to_string())
+            .collect::<Vec<_>>();
+        f.write_str(&elems.join(" mal "))
+    }
+}
+
+// event_cond = noun "'st" verb "-il" "io"
+impl Format for EventCond {
+    fn format(&self, f: &mut Formatter<'_>, config: &LexerConfig) -> fmt::Result {
+        write!(
+            f,
+            "{}'st {}-il io",
+            configured(&self.noun, config),
+            configured(&self.verb, config)
+        )
+    }
+}
+
+// import = "lus" (module "'d")+ (ident | ident "ad" ident | ident "adit" ident ("," ident)+)
+impl Format for Import {
+    fn format(&self, f: &mut Formatter<'_>, config: &LexerConfig) -> fmt::Result {
+        f.write_str("lus ")?;
+        for module in &self.module_path {
+            write!(f, "{}'d ", configured(module, config))?;
+        }
+        write_list(f, &self.idents, config)
+    }
+}
+
+// procedure ("melx" "shrlo" procedure)*
+fn write_procedure_chain(
+    f: &mut Formatter<'_>,
+    procedures: &[Procedure],
+    config: &LexerConfig,
+) -> fmt::Result {
+    let procedures = procedures
+        .iter()
+        .map(|procedure| configured(procedure, config).to_string())
+        .collect::<Vec<_>>();
+    f.write_str(&procedures.join("\nmelx shrlo "))
+}
+
+impl Format for Sentence {
+    fn format(&self, f: &mut Formatter<'_>, config: &LexerConfig) -> fmt::Result {
+        match self {
+            Self::Procedure(procedure) => procedure.format(f, config),
+            Self::Import(import) => import.format(f, config),
+            Self::VarDecl(noun1, noun2) => write!(
+                f,
+                "{} es {}",
+                configured(noun1, config),
+                configured(noun2, config)
+            ),
+            Self::PredicateDecl {
+                noun_list,
+                verb,
+                cond,
+                ..
+            } => {
+                write_list(f, noun_list, config)?;
+                write!(
+                    f,
+                    "'st {}-o {} {}",
+                    configured(verb, config),
+                    config.colon,
+                    configured(cond, config)
+                )
+            }
+            Self::EventHandler {
+                event_conds,
+                procedures,
+                ..
+            } => {
+                for event_cond in event_conds {
+                    write!(f, "{} ", configured(event_cond, config))?;
+                }
+                f.write_str("elx shrlo ")?;
+                write_procedure_chain(f, procedures, config)
+            }
+            Self::GuardedEventHandler {
+                event_conds,
+                guard,
+                procedures,
+                ..
+            } => {
+                for event_cond in event_conds {
+                    write!(f, "{} ", configured(event_cond, config))?;
+                }
+                write!(f, "{} felx shrlo ", configured(guard, config))?;
+                write_procedure_chain(f, procedures, config)
+            }
+        }
+    }
+}
+
+// The nodes display in the default configuration
+macro_rules! display_with_default_config {
+    ($($node:ty),*) => {
+        $(
+            impl Display for $node {
+                fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                    self.format(f, &LexerConfig::default())
+                }
+            }
+        )*
+    };
+}
+
+display_with_default_config!(
+    PrimaryNoun,
+    Verb,
+    Module,
+    Noun,
+    NounsWithCase,
+    NamedParameter,
+    MeaElem,
+    Procedure,
+    CondElem,
+    Cond,
+    EventCond,
+    Import,
+    Sentence
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, span::WithoutSpans};
+
+    const SELSURLE_FORMATTED: &str = "lus jmk4'd jerldir adit kernumesaxm, deln.
+
+selsurle es iu.
+
+laozia jerldir lerj 10 ad 10 el 168 ad 218.
+
+is jerldir'd xakant <selsurle>'c.
+
+kernumesaxm'st text-il io elx shrlo lexisnain jerldir fal kernumesaxm'd snelyo mea xerfo es 1 ad 1 mal dejix es deln
+melx shrlo is selsurle iulo'c.
+
+kernumesaxm'st sides-il io elx shrlo is selsurle iu'c.
+
+nert ad ektir'st es_tydivexy-o : ektir mol cecioj 4 ad 204 mal nert mol cecioj 24 ad 154.
+
+kernumesaxm'st fudiur-il io selsurle mol mal kernumesaxm'd pestavilersnelyo es_tydivexy mal kernumesaxm'd snelyo es_tydivexy felx shrlo xes jerldir lerj kernumesaxm'd pestavilersnelyo el kernumesaxm'd snelyo mea dejix es deln.
+";
+
+    #[test]
+    fn selsurle() {
+        let sentences = parse_program(include_str!("../selsurle.jmk4")).unwrap();
+        assert_eq!(format_program(&sentences), SELSURLE_FORMATTED);
+    }
+
+    #[test]
+    fn round_trip() {
+        let sentences = parse_program(include_str!("../selsurle.jmk4")).unwrap();
+        let formatted = format_program(&sentences);
+        assert_eq!(
+            parse_program(&formatted).unwrap().without_spans(),
+            sentences.without_spans()
+        );
+        assert_eq!(
+            format_program(&parse_program(&formatted).unwrap()),
+            formatted
+        );
+    }
+
+    #[test]
+    fn messy_input() {
+        let sentences = parse_program(
+            "lus  jmk4'd a'd  jerldir ad deln .is jerldir<selsurle>  'c.elx shrlo is selsurle iu 'c melx shrlo is selsurle a adit b,c ,d'c .",
+        )
+        .unwrap();
+        assert_eq!(
+            format_program(&sentences),
+            "lus jmk4'd a'd jerldir ad deln.
+
+is jerldir <selsurle>'c.
+
+elx shrlo is selsurle iu'c
+melx shrlo is selsurle a adit b, c, d'c.
+"
+        );
+    }
+
+    #[test]
+    fn instrumental_case() {
+        let formatted = format_program(&parse_program("is selsurle  <iu>'i .").unwrap());
+        assert_eq!(formatted, "is selsurle <iu>'i.\n");
+    }
+
+    #[test]
+    fn comments() {
+        let cst = Cst::parse(
+            "# header\n\nselsurle  es iu. # iu\n#{ block\n comment }#\nis selsurle # in the middle\n iulo'c.\n\n# footer\n",
+        )
+        .unwrap();
+        let formatted = format_cst(&cst).unwrap();
+        assert_eq!(
+            formatted,
+            "# header
+selsurle es iu. # iu
+
+#{ block
+ comment }#
+# in the middle
+is selsurle iulo'c.
+
+# footer
+"
+        );
+        assert_eq!(
+            format_cst(&Cst::parse(&formatted).unwrap()).unwrap(),
+            formatted
+        );
+    }
+
+    #[test]
+    fn without_comments() {
+        let source = include_str!("../selsurle.jmk4");
+        assert_eq!(
+            format_cst(&Cst::parse(source).unwrap()).unwrap(),
+            SELSURLE_FORMATTED
+        );
+    }
+
+    #[test]
+    fn native_config() {
+        let config = LexerConfig {
+            period: '。',
+            comma: '、',
+            colon: '：',
+            string_literal_open: '「',
+            string_literal_close: '」',
+            ..LexerConfig::unicode_xid()
+        };
+        let cst = Cst::parse_with(
+            "lus jmk4'd jerldir adit kernumesaxm、deln。 # 輸入\n\
+             nert ad ektir'st es_tydivexy-o：ektir mol cecioj 1.5 ad 204。\n\
+             is jerldir'd xakant 「選択」'c。",
+            &config,
+        )
+        .unwrap();
+        let formatted = format_cst(&cst).unwrap();
+        assert_eq!(
+            formatted,
+            "lus jmk4'd jerldir adit kernumesaxm、 deln。 # 輸入
+
+nert ad ektir'st es_tydivexy-o ： ektir mol cecioj 1.5 ad 204。
+
+is jerldir'd xakant 「選択」'c。
+"
+        );
+        let reread = Cst::parse_with(&formatted, &config).unwrap();
+        assert_eq!(
+            reread.to_ast().unwrap().without_spans(),
+            cst.to_ast().unwrap().without_spans()
+        );
+        assert_eq!(
+            format_program_with(&cst.to_ast().unwrap(), &config),
+            format_cst(&reread).unwrap().replace(" # 輸入", "")
+        );
+    }
+}
@@ -1,68 +1,319 @@
 #![warn(clippy::pedantic, clippy::nursery)]
 #![allow(clippy::missing_panics_doc)]
 
-use parser::NounsWithCase;
-
-use crate::{
+use crate::parser::State;
+pub use crate::{
+    cst::{Cst, CstSentence, CstToken, Trivia, TriviaKind},
+    format::{format_cst, format_program, format_program_with},
+    loader::{ImportError, ImportErrorKind, LoadError, LoadedModule, ModuleLoader, BUILTIN_MODULE},
+    messages::Locale,
     parser::{
-        Case, Cond, CondElem, Import, Module, Noun, PrimaryNoun, Procedure, Sentence, State, Verb,
+        Case, Cond, CondElem, EventCond, Expected, Import, MeaElem, Module, NamedParameter, Noun,
+        NounId, NounsWithCase, ParseError, PrimaryNoun, Procedure, Rule, Sentence, Verb,
+    },
+    predicate::{check_predicates, PredicateError, PredicateErrorKind},
+    resolve::{
+        resolve, resolve_with, Definition, DefinitionKind, Resolution, ResolveError,
+        ResolveErrorKind, BUILTINS,
     },
-    token::{Preposition, Reserved, Token},
+    signature::{
+        Arity, CaseSignature, NamedParameterSignature, SignatureError, SignatureErrorKind,
+        SignatureRegistry, VerbSignature, JMK4_SIGNATURES,
+    },
+    span::{Position, Span, WithoutSpans},
+    stream::{tokenize_reader, ReadTokens, StreamError, StreamingLexer},
+    symbol::Symbol,
+    token::{Number, Preposition, Reserved, Token, TokenKind},
+    tokenize::{Alphabet, LexError, LexErrorKind, LexerConfig},
+    transliterate::{Transliterated, Transliteration},
+    types::{check_types, Type, TypeError, TypeErrorKind, TypeTable, Types, JMK4_TYPES},
 };
+mod cst;
+pub mod diagnostics;
+mod format;
+mod loader;
+mod messages;
 mod parser;
+mod predicate;
+mod resolve;
+mod signature;
+mod span;
+mod stream;
+mod symbol;
 mod token;
 mod tokenize;
+mod transliterate;
+mod types;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Error {
+    Lex(LexError),
+    Parse(ParseError),
+    Resolve(ResolveError),
+    Import(ImportError),
+    Signature(SignatureError),
+    Type(TypeError),
+    Predicate(PredicateError),
+}
+
+impl Error {
+    #[must_use]
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Lex(err) => err.span,
+            Self::Parse(err) => err.span(),
+            Self::Resolve(err) => err.span,
+            Self::Import(err) => err.span,
+            Self::Signature(err) => err.span,
+            Self::Type(err) => err.span,
+            Self::Predicate(err) => err.span,
+        }
+    }
+
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::Lex(err) => err.code(),
+            Self::Parse(err) => err.code(),
+            Self::Resolve(err) => err.code(),
+            Self::Import(err) => err.code(),
+            Self::Signature(err) => err.code(),
+            Self::Type(err) => err.code(),
+            Self::Predicate(err) => err.code(),
+        }
+    }
+
+    /// The one-line message of the error, in the language of `locale`.
+    #[must_use]
+    pub fn message(&self, locale: Locale) -> String {
+        diagnostics::Diagnostic::new(self, locale).message
+    }
+}
+
+impl From<LexError> for Error {
+    fn from(err: LexError) -> Self {
+        Self::Lex(err)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl From<ResolveError> for Error {
+    fn from(err: ResolveError) -> Self {
+        Self::Resolve(err)
+    }
+}
+
+impl From<ImportError> for Error {
+    fn from(err: ImportError) -> Self {
+        Self::Import(err)
+    }
+}
+
+impl From<SignatureError> for Error {
+    fn from(err: SignatureError) -> Self {
+        Self::Signature(err)
+    }
+}
+
+impl From<TypeError> for Error {
+    fn from(err: TypeError) -> Self {
+        Self::Type(err)
+    }
+}
+
+impl From<PredicateError> for Error {
+    fn from(err: PredicateError) -> Self {
+        Self::Predicate(err)
+    }
+}
+
+/// Parses a whole `.jmk4` source, i.e. `program = (sentence ".")*`.
+///
+/// # Errors
+/// Returns the `LexError` or the first `ParseError` encountered.
+#[allow(clippy::result_large_err)]
+pub fn parse_program(input: &str) -> Result<Vec<Sentence>, Error> {
+    parse_program_with_config(input, &LexerConfig::default())
+}
+
+/// Parses a whole `.jmk4` source like `parse_program`, with the character set of `config`.
+///
+/// # Errors
+/// Returns the `LexError` or the first `ParseError` encountered.
+#[allow(clippy::result_large_err)]
+pub fn parse_program_with_config(
+    input: &str,
+    config: &LexerConfig,
+) -> Result<Vec<Sentence>, Error> {
+    Ok(Cst::parse_with(input, config)?.to_ast()?)
+}
+
+/// Parses a whole `.jmk4` source like `parse_program`, reading it through `transliteration`
+/// first. Error spans point at `input` as written.
+///
+/// # Errors
+/// Returns the `LexError` or the first `ParseError` encountered.
+#[allow(clippy::result_large_err)]
+pub fn parse_program_transliterated(
+    input: &str,
+    transliteration: &Transliteration,
+) -> Result<Vec<Sentence>, Error> {
+    let config = LexerConfig::default();
+    Ok(Cst::parse_transliterated(input, transliteration, &config)?.to_ast()?)
+}
+
+/// Parses a whole `.jmk4` source like `parse_program` and resolves its names.
+///
+/// # Errors
+/// Returns the `LexError` or the first `ParseError`, or else every `ResolveError` found.
+pub fn resolve_program(input: &str) -> Result<(Vec<Sentence>, Resolution), Vec<Error>> {
+    let sentences = parse_program(input).map_err(|err| vec![err])?;
+    let (resolution, errors) = resolve(&sentences);
+    if errors.is_empty() {
+        Ok((sentences, resolution))
+    } else {
+        Err(errors.into_iter().map(Error::from).collect())
+    }
+}
+
+/// Parses a whole `.jmk4` source like `parse_program`, resolves its names and checks it against
+/// the signatures and types of the `jmk4` module and its predicate declarations.
+///
+/// # Errors
+/// Returns the `LexError` or the first `ParseError`, or else every error the checks found.
+pub fn check_program(input: &str) -> Result<(Vec<Sentence>, Resolution, Types), Vec<Error>> {
+    let sentences = parse_program(input).map_err(|err| vec![err])?;
+    let (resolution, resolve_errors) = resolve(&sentences);
+    let signatures = SignatureRegistry::jmk4();
+    let signature_errors = signatures.check(&sentences);
+    let (types, type_errors) =
+        check_types(&sentences, &resolution, &signatures, &TypeTable::jmk4());
+    let predicate_errors = check_predicates(&sentences, &resolution);
+    let errors = resolve_errors
+        .into_iter()
+        .map(Error::from)
+        .chain(signature_errors.into_iter().map(Error::from))
+        .chain(type_errors.into_iter().map(Error::from))
+        .chain(predicate_errors.into_iter().map(Error::from))
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        Ok((sentences, resolution, types))
+    } else {
+        Err(errors)
+    }
+}
+
+/// Parses a whole `.jmk4` source like `parse_program`, but does not stop at the first error:
+/// a sentence with a lexical or syntax error is skipped up to its `.` and parsing resumes from
+/// the next one.
+///
+/// Returns the sentences that could be parsed and every error found, in source order.
+#[must_use]
+pub fn parse_program_with_recovery(input: &str) -> (Vec<Sentence>, Vec<Error>) {
+    let (tokens, lex_errors) = token::tokenize_with_recovery(input, &LexerConfig::default());
+    let mut lex_errors = lex_errors.into_iter().peekable();
+    let mut sentences = vec![];
+    let mut errors = vec![];
+    let is_period = |tok: &Token| tok.kind == TokenKind::Reserved(Reserved::PunctuationPeriod);
+    for tokens in tokens.split_inclusive(is_period) {
+        // the sentence goes on up to its `.`, or to the end of the input without one
+        let end = match tokens.last() {
+            Some(tok) if is_period(tok) => tok.span.end.offset,
+            _ => input.len(),
+        };
+        let mut lexed = true;
+        while let Some(err) = lex_errors.next_if(|err| err.span.start.offset < end) {
+            errors.push(err.into());
+            lexed = false;
+        }
+        if !lexed {
+            continue;
+        }
+        match State::new(tokens).parse_terminated_sentence() {
+            Ok(sentence) => sentences.push(sentence),
+            Err(err) => errors.push(err.into()),
+        }
+    }
+    errors.extend(lex_errors.map(Error::from));
+    (sentences, errors)
+}
 
 #[must_use]
 pub fn noun_from_ident(ident: &str) -> Noun {
     Noun {
         modifier: vec![],
         head: PrimaryNoun::Ident {
-            ident: ident.to_string(),
+            ident: Symbol::from(ident),
+            span: Span::default(),
         },
+        span: Span::default(),
+        id: NounId::default(),
+    }
+}
+
+#[must_use]
+pub fn noun_from_integer(value: i64) -> Noun {
+    Noun {
+        modifier: vec![],
+        head: PrimaryNoun::Number {
+            number: Number::integer(value),
+            span: Span::default(),
+        },
+        span: Span::default(),
+        id: NounId::default(),
     }
 }
 
 #[must_use]
 pub fn primary_noun_from_ident(ident: &str) -> PrimaryNoun {
     PrimaryNoun::Ident {
-        ident: ident.to_string(),
+        ident: Symbol::from(ident),
+        span: Span::default(),
     }
 }
 
 pub fn test_primary_noun() {
-    let tokens = token::tokenize("xakant");
+    let tokens = token::tokenize("xakant").unwrap();
     let mut parser_state = State::new(&tokens);
     let noun = parser_state.parse_primary_noun().unwrap();
     assert!(parser_state.is_empty());
-    assert_eq!(noun, primary_noun_from_ident("xakant"));
+    assert_eq!(noun.without_spans(), primary_noun_from_ident("xakant"));
 }
 
 pub fn test_noun() {
-    let tokens = token::tokenize("jerldir'd xakant");
+    let tokens = token::tokenize("jerldir'd xakant").unwrap();
     let mut parser_state = State::new(&tokens);
     let noun = parser_state.parse_noun().unwrap();
     assert!(parser_state.is_empty());
     assert_eq!(
-        noun,
+        noun.without_spans(),
         Noun {
             modifier: vec![primary_noun_from_ident("jerldir")],
-            head: primary_noun_from_ident("xakant")
+            head: primary_noun_from_ident("xakant"),
+            span: Span::default(),
+            id: NounId::default(),
         }
     );
 }
 
 pub fn test_noun_list() {
-    let tokens = token::tokenize("jerldir'd xakant adit kernumesaxm, deln");
+    let tokens = token::tokenize("jerldir'd xakant adit kernumesaxm, deln").unwrap();
     let mut parser_state = State::new(&tokens);
     let noun = parser_state.parse_noun_list().unwrap();
     assert!(parser_state.is_empty());
     assert_eq!(
-        noun,
+        noun.without_spans(),
         vec![
             Noun {
                 modifier: vec![primary_noun_from_ident("jerldir")],
-                head: primary_noun_from_ident("xakant")
+                head: primary_noun_from_ident("xakant"),
+                span: Span::default(),
+                id: NounId::default(),
             },
             noun_from_ident("kernumesaxm"),
             noun_from_ident("deln"),
@@ -71,83 +322,128 @@ pub fn test_noun_list() {
 }
 
 pub fn test_nouns_with_case() {
-    let tokens = token::tokenize("lerj 10 ad 10");
+    let tokens = token::tokenize("lerj 10 ad 10").unwrap();
     let mut parser_state = State::new(&tokens);
     let noun = parser_state.parse_nouns_with_case().unwrap();
     assert!(parser_state.is_empty());
     assert_eq!(
-        noun,
+        noun.without_spans(),
         NounsWithCase {
             case: parser::Case::Preposition(token::Preposition::Lerj),
-            nouns: vec![noun_from_ident("10"), noun_from_ident("10")]
+            nouns: vec![noun_from_integer(10), noun_from_integer(10)],
+            span: Span::default()
+        }
+    );
+
+    let tokens = token::tokenize("jerldir ad deln'i").unwrap();
+    let mut parser_state = State::new(&tokens);
+    let noun = parser_state.parse_nouns_with_case().unwrap();
+    assert!(parser_state.is_empty());
+    assert_eq!(
+        noun.without_spans(),
+        NounsWithCase {
+            case: Case::ApostropheI,
+            nouns: vec![noun_from_ident("jerldir"), noun_from_ident("deln")],
+            span: Span::default()
+        }
+    );
+
+    let tokens = token::tokenize("jerldir'st").unwrap();
+    let err = State::new(&tokens).parse_nouns_with_case().unwrap_err();
+    assert_eq!(
+        err.without_spans(),
+        ParseError::UnexpectedToken {
+            expected: vec![
+                Expected::Reserved(Reserved::ApostropheC),
+                Expected::Reserved(Reserved::ApostropheI)
+            ],
+            rule: Rule::NounsWithCase,
+            actual: Token {
+                kind: TokenKind::Reserved(Reserved::ApostropheSt),
+                span: Span::default()
+            }
         }
     );
 }
 
 pub fn test_var_decl() {
-    let tokens = token::tokenize("selsurle es iu.");
+    let tokens = token::tokenize("selsurle es iu.").unwrap();
     let mut parser_state = State::new(&tokens);
     let sentence = parser_state.parse_var_decl().unwrap();
     assert_eq!(
-        parser_state.get_tokens(),
-        vec![Token::Reserved(Reserved::PunctuationPeriod)]
+        parser_state.get_tokens().to_vec().without_spans(),
+        vec![Token {
+            kind: TokenKind::Reserved(Reserved::PunctuationPeriod),
+            span: Span::default()
+        }]
     );
     assert_eq!(
-        sentence,
+        sentence.without_spans(),
         Sentence::VarDecl(noun_from_ident("selsurle"), noun_from_ident("iu"))
     );
 }
 
 pub fn test_cond() {
-    let tokens = token::tokenize("selsurle mol mal kernumesaxm'd pestavilersnelyo es_tydivexy mal kernumesaxm'd snelyo es_tydivexy");
+    let tokens = token::tokenize("selsurle mol mal kernumesaxm'd pestavilersnelyo es_tydivexy mal kernumesaxm'd snelyo es_tydivexy").unwrap();
     let mut parser_state = State::new(&tokens);
     let cond = parser_state.parse_cond().unwrap();
-    assert!(tokens.is_empty());
+    assert!(parser_state.is_empty());
     assert_eq!(
-        cond,
+        cond.without_spans(),
         Cond(vec![
             CondElem {
                 noun: noun_from_ident("selsurle"),
-                verb: Verb("mol".to_string()),
-                nouns_with_case: None,
+                verb: Verb(Symbol::from("mol"), Span::default()),
+                nouns_with_case_array: vec![],
+                span: Span::default(),
             },
             CondElem {
                 noun: Noun {
                     modifier: vec![primary_noun_from_ident("kernumesaxm")],
-                    head: primary_noun_from_ident("pestavilersnelyo")
+                    head: primary_noun_from_ident("pestavilersnelyo"),
+                    span: Span::default(),
+                    id: NounId::default(),
                 },
-                verb: Verb("es_tydivexy".to_string()),
-                nouns_with_case: None,
+                verb: Verb(Symbol::from("es_tydivexy"), Span::default()),
+                nouns_with_case_array: vec![],
+                span: Span::default(),
             },
             CondElem {
                 noun: Noun {
                     modifier: vec![primary_noun_from_ident("kernumesaxm")],
-                    head: primary_noun_from_ident("snelyo")
+                    head: primary_noun_from_ident("snelyo"),
+                    span: Span::default(),
+                    id: NounId::default(),
                 },
-                verb: Verb("es_tydivexy".to_string()),
-                nouns_with_case: None,
+                verb: Verb(Symbol::from("es_tydivexy"), Span::default()),
+                nouns_with_case_array: vec![],
+                span: Span::default(),
             },
         ])
     );
 }
 
 pub fn test_import() {
-    let tokens = token::tokenize("lus jmk4'd jerldir adit kernumesaxm, deln.");
+    let tokens = token::tokenize("lus jmk4'd jerldir adit kernumesaxm, deln.").unwrap();
     let mut parser_state = State::new(&tokens);
     let import = parser_state.parse_import().unwrap();
     assert_eq!(
-        parser_state.get_tokens(),
-        vec![Token::Reserved(Reserved::PunctuationPeriod)]
+        parser_state.get_tokens().to_vec().without_spans(),
+        vec![Token {
+            kind: TokenKind::Reserved(Reserved::PunctuationPeriod),
+            span: Span::default()
+        }]
     );
     assert_eq!(
-        import,
+        import.without_spans(),
         Import {
-            module_path: vec![Module("jmk4".to_string())],
+            module_path: vec![Module(Symbol::from("jmk4"), Span::default())],
             idents: vec![
-                "jerldir".to_string(),
-                "kernumesaxm".to_string(),
-                "deln".to_string()
-            ]
+                Symbol::from("jerldir"),
+                Symbol::from("kernumesaxm"),
+                Symbol::from("deln")
+            ],
+            span: Span::default()
         }
     );
 }
@@ -155,66 +451,603 @@ pub fn test_import() {
 pub fn test_predicate_decl() {
     let tokens = token::tokenize(
         "nert ad ektir'st es_tydivexy-o : ektir mol cecioj 4 ad 204 mal nert mol cecioj 24 ad 154.",
-    );
+    )
+    .unwrap();
     let mut parser_state = State::new(&tokens);
     let predicate = parser_state.parse_predicate_decl().unwrap();
     assert_eq!(
-        parser_state.get_tokens(),
-        vec![Token::Reserved(Reserved::PunctuationPeriod)]
+        parser_state.get_tokens().to_vec().without_spans(),
+        vec![Token {
+            kind: TokenKind::Reserved(Reserved::PunctuationPeriod),
+            span: Span::default()
+        }]
     );
     assert_eq!(
-        predicate,
+        predicate.without_spans(),
         Sentence::PredicateDecl {
             noun_list: vec![noun_from_ident("nert"), noun_from_ident("ektir")],
-            verb: Verb("es_tydivexy".to_string()),
+            verb: Verb(Symbol::from("es_tydivexy"), Span::default()),
             cond: Cond(vec![
                 CondElem {
                     noun: noun_from_ident("ektir"),
-                    verb: Verb("mol".to_string()),
-                    nouns_with_case: Some(NounsWithCase {
-                        nouns: vec![noun_from_ident("4"), noun_from_ident("204")],
-                        case: parser::Case::Preposition(token::Preposition::Cecioj)
-                    })
+                    verb: Verb(Symbol::from("mol"), Span::default()),
+                    nouns_with_case_array: vec![NounsWithCase {
+                        nouns: vec![noun_from_integer(4), noun_from_integer(204)],
+                        case: parser::Case::Preposition(token::Preposition::Cecioj),
+                        span: Span::default()
+                    }],
+                    span: Span::default()
                 },
                 CondElem {
                     noun: noun_from_ident("nert"),
-                    verb: Verb("mol".to_string()),
-                    nouns_with_case: Some(NounsWithCase {
-                        nouns: vec![noun_from_ident("24"), noun_from_ident("154")],
-                        case: parser::Case::Preposition(token::Preposition::Cecioj)
-                    })
+                    verb: Verb(Symbol::from("mol"), Span::default()),
+                    nouns_with_case_array: vec![NounsWithCase {
+                        nouns: vec![noun_from_integer(24), noun_from_integer(154)],
+                        case: parser::Case::Preposition(token::Preposition::Cecioj),
+                        span: Span::default()
+                    }],
+                    span: Span::default()
                 }
-            ])
+            ]),
+            span: Span::default()
         }
     );
 }
 
 pub fn test_procedure() {
-    let tokens = token::tokenize("laozia jerldir lerj 10 ad 10 el 168 ad 218.");
+    let tokens = token::tokenize("laozia jerldir lerj 10 ad 10 el 168 ad 218.").unwrap();
     let mut parser_state = State::new(&tokens);
     let procedure = parser_state.parse_procedure().unwrap();
     assert_eq!(
-        parser_state.get_tokens(),
-        vec![Token::Reserved(Reserved::PunctuationPeriod)]
+        parser_state.get_tokens().to_vec().without_spans(),
+        vec![Token {
+            kind: TokenKind::Reserved(Reserved::PunctuationPeriod),
+            span: Span::default()
+        }]
     );
     assert_eq!(
-        procedure,
+        procedure.without_spans(),
         Procedure {
-            verb: Verb("laozia".to_string()),
+            verb: Verb(Symbol::from("laozia"), Span::default()),
             noun: noun_from_ident("jerldir"),
             nouns_with_case_array: vec![
                 NounsWithCase {
-                    nouns: vec![noun_from_ident("10"), noun_from_ident("10")],
-                    case: Case::Preposition(Preposition::Lerj)
+                    nouns: vec![noun_from_integer(10), noun_from_integer(10)],
+                    case: Case::Preposition(Preposition::Lerj),
+                    span: Span::default()
                 },
                 NounsWithCase {
-                    nouns: vec![noun_from_ident("168"), noun_from_ident("218")],
-                    case: Case::Preposition(Preposition::El)
+                    nouns: vec![noun_from_integer(168), noun_from_integer(218)],
+                    case: Case::Preposition(Preposition::El),
+                    span: Span::default()
                 }
             ],
-            mea_clause: None
+            mea_clause: None,
+            span: Span::default()
+        }
+    );
+}
+
+pub fn test_sentence_dispatch() {
+    let sentences = parse_program(
+        "lus jmk4'd deln. selsurle es iu. laozia jerldir lerj 10 ad 10. \
+         nert'st es_tydivexy-o : nert mol. \
+         kernumesaxm'st sides-il io elx shrlo is selsurle iu'c. \
+         kernumesaxm'st fudiur-il io selsurle mol felx shrlo is selsurle iu'c.",
+    )
+    .unwrap();
+    assert!(matches!(sentences[0], Sentence::Import(_)));
+    assert!(matches!(sentences[1], Sentence::VarDecl(_, _)));
+    assert!(matches!(sentences[2], Sentence::Procedure(_)));
+    assert!(matches!(sentences[3], Sentence::PredicateDecl { .. }));
+    assert!(matches!(sentences[4], Sentence::EventHandler { .. }));
+    assert!(matches!(sentences[5], Sentence::GuardedEventHandler { .. }));
+    assert_eq!(sentences.len(), 6);
+}
+
+pub fn test_event_handler() {
+    let tokens = token::tokenize(
+        "jerldir'st sides-il io kernumesaxm'st text-il io elx shrlo is selsurle iu'c melx shrlo is selsurle iulo'c.",
+    ).unwrap();
+    let mut parser_state = State::new(&tokens);
+    let handler = parser_state.parse_event_handler().unwrap();
+    assert_eq!(
+        parser_state.get_tokens().to_vec().without_spans(),
+        vec![Token {
+            kind: TokenKind::Reserved(Reserved::PunctuationPeriod),
+            span: Span::default()
+        }]
+    );
+    let is_selsurle = |value: &str| Procedure {
+        verb: Verb(Symbol::from("is"), Span::default()),
+        noun: noun_from_ident("selsurle"),
+        nouns_with_case_array: vec![NounsWithCase {
+            nouns: vec![noun_from_ident(value)],
+            case: Case::ApostropheC,
+            span: Span::default(),
+        }],
+        mea_clause: None,
+        span: Span::default(),
+    };
+    assert_eq!(
+        handler.without_spans(),
+        Sentence::EventHandler {
+            event_conds: vec![
+                EventCond {
+                    noun: noun_from_ident("jerldir"),
+                    verb: Verb(Symbol::from("sides"), Span::default()),
+                    span: Span::default(),
+                },
+                EventCond {
+                    noun: noun_from_ident("kernumesaxm"),
+                    verb: Verb(Symbol::from("text"), Span::default()),
+                    span: Span::default(),
+                },
+            ],
+            procedures: vec![is_selsurle("iu"), is_selsurle("iulo")],
+            span: Span::default()
+        }
+    );
+}
+
+pub fn test_event_handler_without_event_cond() {
+    let sentences = parse_program("elx shrlo laozia jerldir lerj 10 ad 10.").unwrap();
+    assert_eq!(
+        sentences.without_spans(),
+        vec![Sentence::EventHandler {
+            event_conds: vec![],
+            procedures: vec![Procedure {
+                verb: Verb(Symbol::from("laozia"), Span::default()),
+                noun: noun_from_ident("jerldir"),
+                nouns_with_case_array: vec![NounsWithCase {
+                    nouns: vec![noun_from_integer(10), noun_from_integer(10)],
+                    case: Case::Preposition(Preposition::Lerj),
+                    span: Span::default()
+                }],
+                mea_clause: None,
+                span: Span::default()
+            }],
+            span: Span::default()
+        }]
+    );
+}
+
+pub fn test_guarded_event_handler() {
+    let tokens = token::tokenize(
+        "kernumesaxm'st fudiur-il io jerldir mol cecioj 4 ad 204 el 1 mal selsurle mol felx shrlo is selsurle iu'c melx shrlo is selsurle iulo'c.",
+    ).unwrap();
+    let mut parser_state = State::new(&tokens);
+    let handler = parser_state.parse_event_handler().unwrap();
+    assert_eq!(
+        parser_state.get_tokens().to_vec().without_spans(),
+        vec![Token {
+            kind: TokenKind::Reserved(Reserved::PunctuationPeriod),
+            span: Span::default()
+        }]
+    );
+    let is_selsurle = |value: &str| Procedure {
+        verb: Verb(Symbol::from("is"), Span::default()),
+        noun: noun_from_ident("selsurle"),
+        nouns_with_case_array: vec![NounsWithCase {
+            nouns: vec![noun_from_ident(value)],
+            case: Case::ApostropheC,
+            span: Span::default(),
+        }],
+        mea_clause: None,
+        span: Span::default(),
+    };
+    assert_eq!(
+        handler.without_spans(),
+        Sentence::GuardedEventHandler {
+            event_conds: vec![EventCond {
+                noun: noun_from_ident("kernumesaxm"),
+                verb: Verb(Symbol::from("fudiur"), Span::default()),
+                span: Span::default(),
+            }],
+            guard: Cond(vec![
+                CondElem {
+                    noun: noun_from_ident("jerldir"),
+                    verb: Verb(Symbol::from("mol"), Span::default()),
+                    nouns_with_case_array: vec![
+                        NounsWithCase {
+                            nouns: vec![noun_from_integer(4), noun_from_integer(204)],
+                            case: Case::Preposition(Preposition::Cecioj),
+                            span: Span::default()
+                        },
+                        NounsWithCase {
+                            nouns: vec![noun_from_integer(1)],
+                            case: Case::Preposition(Preposition::El),
+                            span: Span::default()
+                        }
+                    ],
+                    span: Span::default(),
+                },
+                CondElem {
+                    noun: noun_from_ident("selsurle"),
+                    verb: Verb(Symbol::from("mol"), Span::default()),
+                    nouns_with_case_array: vec![],
+                    span: Span::default(),
+                },
+            ]),
+            procedures: vec![is_selsurle("iu"), is_selsurle("iulo")],
+            span: Span::default()
+        }
+    );
+}
+
+pub fn test_sentences_in_source_order() {
+    let sentences = parse_program(
+        "lus jmk4'd jerldir ad kernumesaxm. is jerldir'd xakant <selsurle>'c. lus jmk4'd deln.",
+    )
+    .unwrap();
+    assert_eq!(
+        sentences.without_spans(),
+        vec![
+            Sentence::Import(Import {
+                module_path: vec![Module(Symbol::from("jmk4"), Span::default())],
+                idents: vec![Symbol::from("jerldir"), Symbol::from("kernumesaxm")],
+                span: Span::default()
+            }),
+            Sentence::Procedure(Procedure {
+                verb: Verb(Symbol::from("is"), Span::default()),
+                noun: Noun {
+                    modifier: vec![primary_noun_from_ident("jerldir")],
+                    head: primary_noun_from_ident("xakant"),
+                    span: Span::default(),
+                    id: NounId::default(),
+                },
+                nouns_with_case_array: vec![NounsWithCase {
+                    nouns: vec![Noun {
+                        modifier: vec![],
+                        head: PrimaryNoun::StringLiteral {
+                            literal: Symbol::from("<selsurle>"),
+                            value: Symbol::from("selsurle"),
+                            span: Span::default()
+                        },
+                        span: Span::default(),
+                        id: NounId::default(),
+                    }],
+                    case: Case::ApostropheC,
+                    span: Span::default()
+                }],
+                mea_clause: None,
+                span: Span::default()
+            }),
+            Sentence::Import(Import {
+                module_path: vec![Module(Symbol::from("jmk4"), Span::default())],
+                idents: vec![Symbol::from("deln")],
+                span: Span::default()
+            }),
+        ]
+    );
+}
+
+pub fn test_spans() {
+    let sentences =
+        parse_program("selsurle es iu.\nlaozia jerldir'd xakant lerj 10 ad 10.").unwrap();
+    assert_eq!(sentences[0].span().range(), 0..14);
+    let Sentence::Procedure(procedure) = &sentences[1] else {
+        panic!("expected a procedure");
+    };
+    assert_eq!(procedure.span.range(), 16..53);
+    assert_eq!(procedure.verb.1.range(), 16..22);
+    assert_eq!(procedure.noun.span.range(), 23..39);
+    assert_eq!(procedure.noun.head.span().range(), 33..39);
+    assert_eq!(procedure.nouns_with_case_array[0].span.range(), 40..53);
+    assert_eq!(
+        procedure.nouns_with_case_array[0].nouns[1].span.start,
+        Position {
+            offset: 51,
+            line: 2,
+            column: 36
+        }
+    );
+}
+
+pub fn test_parse_error_spans() {
+    let err = parse_program("selsurle es iu.\nselsurle es .").unwrap_err();
+    assert_eq!(err.code(), "E0001");
+    let Error::Parse(parse_err) = &err else {
+        panic!("{err:?}");
+    };
+    assert_eq!(
+        parse_err.without_spans(),
+        ParseError::UnexpectedToken {
+            expected: vec![Expected::Ident, Expected::StringLiteral, Expected::Number],
+            rule: Rule::PrimaryNoun,
+            actual: Token {
+                kind: TokenKind::Reserved(Reserved::PunctuationPeriod),
+                span: Span::default()
+            }
         }
     );
+    assert_eq!(err.span().range(), 28..29);
+    assert_eq!(err.message(Locale::Japanese), "予期しないトークン `.` です");
+    assert_eq!(err.message(Locale::English), "unexpected token `.`");
+    assert_eq!(err.span().start.line, 2);
+    let err = parse_program("selsurle es").unwrap_err();
+    let end = Position {
+        offset: 11,
+        line: 1,
+        column: 12,
+    };
+    assert_eq!(
+        err,
+        Error::Parse(ParseError::EndOfFile {
+            expected: vec![Expected::Ident, Expected::StringLiteral, Expected::Number],
+            rule: Rule::PrimaryNoun,
+            span: Span::new(end, end)
+        })
+    );
+    // a procedure cut short at the end of the input still needs its `.`
+    let err = parse_program("is jerldir").unwrap_err();
+    let end = Position {
+        offset: 10,
+        line: 1,
+        column: 11,
+    };
+    assert_eq!(
+        err,
+        Error::Parse(ParseError::EndOfFile {
+            expected: vec![Expected::Reserved(Reserved::PunctuationPeriod)],
+            rule: Rule::Program,
+            span: Span::new(end, end)
+        })
+    );
+    let err = parse_program("selsurle es Iu.").unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Lex(LexError {
+            kind: LexErrorKind::IllegalCharacter('I'),
+            ..
+        })
+    ));
+    assert_eq!(err.span().range(), 12..13);
+}
+
+pub fn test_recovery() {
+    let (sentences, errors) = parse_program_with_recovery(
+        "selsurle es . lus jmk4'd deln. selsurle es iu iu. laozia jerldir lerj 10 ad 10. is jerldir",
+    );
+    assert_eq!(
+        sentences.without_spans(),
+        vec![
+            Sentence::Import(Import {
+                module_path: vec![Module(Symbol::from("jmk4"), Span::default())],
+                idents: vec![Symbol::from("deln")],
+                span: Span::default()
+            }),
+            Sentence::Procedure(Procedure {
+                verb: Verb(Symbol::from("laozia"), Span::default()),
+                noun: noun_from_ident("jerldir"),
+                nouns_with_case_array: vec![NounsWithCase {
+                    nouns: vec![noun_from_integer(10), noun_from_integer(10)],
+                    case: Case::Preposition(Preposition::Lerj),
+                    span: Span::default()
+                }],
+                mea_clause: None,
+                span: Span::default()
+            }),
+        ]
+    );
+    assert_eq!(
+        errors
+            .iter()
+            .map(|err| err.span().range())
+            .collect::<Vec<_>>(),
+        vec![12..13, 46..48, 90..90]
+    );
+
+    // a sentence with a lex error is skipped like one with a syntax error
+    let (sentences, errors) = parse_program_with_recovery(
+        "selsurle es iu. is $ selsurle iu'c. selsurle es <\\q>. deln es iulo. deln es",
+    );
+    assert_eq!(
+        sentences.without_spans(),
+        vec![
+            Sentence::VarDecl(noun_from_ident("selsurle"), noun_from_ident("iu")),
+            Sentence::VarDecl(noun_from_ident("deln"), noun_from_ident("iulo")),
+        ]
+    );
+    assert_eq!(
+        errors
+            .iter()
+            .map(|err| (err.code(), err.span().range()))
+            .collect::<Vec<_>>(),
+        vec![("E0101", 19..20), ("E0106", 49..51), ("E0002", 75..75)]
+    );
+}
+
+/// The AST of `selsurle.jmk4`, written out by hand, without spans.
+#[must_use]
+#[allow(clippy::too_many_lines)]
+pub fn selsurle_sentences() -> Vec<Sentence> {
+    let kernumesaxm_d = |head: &str| Noun {
+        modifier: vec![primary_noun_from_ident("kernumesaxm")],
+        head: primary_noun_from_ident(head),
+        span: Span::default(),
+        id: NounId::default(),
+    };
+    let mea_elem = |head: &str, noun_list: Vec<Noun>| MeaElem {
+        named_parameter: NamedParameter {
+            modifiers: vec![],
+            head: Symbol::from(head),
+            span: Span::default(),
+        },
+        noun_list,
+        span: Span::default(),
+    };
+    let is_selsurle = |value: &str| Procedure {
+        verb: Verb(Symbol::from("is"), Span::default()),
+        noun: noun_from_ident("selsurle"),
+        nouns_with_case_array: vec![NounsWithCase {
+            nouns: vec![noun_from_ident(value)],
+            case: Case::ApostropheC,
+            span: Span::default(),
+        }],
+        mea_clause: None,
+        span: Span::default(),
+    };
+    let event_cond = |verb: &str| EventCond {
+        noun: noun_from_ident("kernumesaxm"),
+        verb: Verb(Symbol::from(verb), Span::default()),
+        span: Span::default(),
+    };
+    vec![
+        Sentence::Import(Import {
+            module_path: vec![Module(Symbol::from("jmk4"), Span::default())],
+            idents: vec![
+                Symbol::from("jerldir"),
+                Symbol::from("kernumesaxm"),
+                Symbol::from("deln"),
+            ],
+            span: Span::default(),
+        }),
+        Sentence::VarDecl(noun_from_ident("selsurle"), noun_from_ident("iu")),
+        Sentence::Procedure(Procedure {
+            verb: Verb(Symbol::from("laozia"), Span::default()),
+            noun: noun_from_ident("jerldir"),
+            nouns_with_case_array: vec![
+                NounsWithCase {
+                    nouns: vec![noun_from_integer(10), noun_from_integer(10)],
+                    case: Case::Preposition(Preposition::Lerj),
+                    span: Span::default(),
+                },
+                NounsWithCase {
+                    nouns: vec![noun_from_integer(168), noun_from_integer(218)],
+                    case: Case::Preposition(Preposition::El),
+                    span: Span::default(),
+                },
+            ],
+            mea_clause: None,
+            span: Span::default(),
+        }),
+        Sentence::Procedure(Procedure {
+            verb: Verb(Symbol::from("is"), Span::default()),
+            noun: Noun {
+                modifier: vec![primary_noun_from_ident("jerldir")],
+                head: primary_noun_from_ident("xakant"),
+                span: Span::default(),
+                id: NounId::default(),
+            },
+            nouns_with_case_array: vec![NounsWithCase {
+                nouns: vec![Noun {
+                    modifier: vec![],
+                    head: PrimaryNoun::StringLiteral {
+                        literal: Symbol::from("<selsurle>"),
+                        value: Symbol::from("selsurle"),
+                        span: Span::default(),
+                    },
+                    span: Span::default(),
+                    id: NounId::default(),
+                }],
+                case: Case::ApostropheC,
+                span: Span::default(),
+            }],
+            mea_clause: None,
+            span: Span::default(),
+        }),
+        Sentence::EventHandler {
+            event_conds: vec![event_cond("text")],
+            procedures: vec![
+                Procedure {
+                    verb: Verb(Symbol::from("lexisnain"), Span::default()),
+                    noun: noun_from_ident("jerldir"),
+                    nouns_with_case_array: vec![NounsWithCase {
+                        nouns: vec![kernumesaxm_d("snelyo")],
+                        case: Case::Preposition(Preposition::Fal),
+                        span: Span::default(),
+                    }],
+                    mea_clause: Some(vec![
+                        mea_elem("xerfo", vec![noun_from_integer(1), noun_from_integer(1)]),
+                        mea_elem("dejix", vec![noun_from_ident("deln")]),
+                    ]),
+                    span: Span::default(),
+                },
+                is_selsurle("iulo"),
+            ],
+            span: Span::default(),
+        },
+        Sentence::EventHandler {
+            event_conds: vec![event_cond("sides")],
+            procedures: vec![is_selsurle("iu")],
+            span: Span::default(),
+        },
+        Sentence::PredicateDecl {
+            noun_list: vec![noun_from_ident("nert"), noun_from_ident("ektir")],
+            verb: Verb(Symbol::from("es_tydivexy"), Span::default()),
+            cond: Cond(vec![
+                CondElem {
+                    noun: noun_from_ident("ektir"),
+                    verb: Verb(Symbol::from("mol"), Span::default()),
+                    nouns_with_case_array: vec![NounsWithCase {
+                        nouns: vec![noun_from_integer(4), noun_from_integer(204)],
+                        case: Case::Preposition(Preposition::Cecioj),
+                        span: Span::default(),
+                    }],
+                    span: Span::default(),
+                },
+                CondElem {
+                    noun: noun_from_ident("nert"),
+                    verb: Verb(Symbol::from("mol"), Span::default()),
+                    nouns_with_case_array: vec![NounsWithCase {
+                        nouns: vec![noun_from_integer(24), noun_from_integer(154)],
+                        case: Case::Preposition(Preposition::Cecioj),
+                        span: Span::default(),
+                    }],
+                    span: Span::default(),
+                },
+            ]),
+            span: Span::default(),
+        },
+        Sentence::GuardedEventHandler {
+            event_conds: vec![event_cond("fudiur")],
+            guard: Cond(vec![
+                CondElem {
+                    noun: noun_from_ident("selsurle"),
+                    verb: Verb(Symbol::from("mol"), Span::default()),
+                    nouns_with_case_array: vec![],
+                    span: Span::default(),
+                },
+                CondElem {
+                    noun: kernumesaxm_d("pestavilersnelyo"),
+                    verb: Verb(Symbol::from("es_tydivexy"), Span::default()),
+                    nouns_with_case_array: vec![],
+                    span: Span::default(),
+                },
+                CondElem {
+                    noun: kernumesaxm_d("snelyo"),
+                    verb: Verb(Symbol::from("es_tydivexy"), Span::default()),
+                    nouns_with_case_array: vec![],
+                    span: Span::default(),
+                },
+            ]),
+            procedures: vec![Procedure {
+                verb: Verb(Symbol::from("xes"), Span::default()),
+                noun: noun_from_ident("jerldir"),
+                nouns_with_case_array: vec![
+                    NounsWithCase {
+                        nouns: vec![kernumesaxm_d("pestavilersnelyo")],
+                        case: Case::Preposition(Preposition::Lerj),
+                        span: Span::default(),
+                    },
+                    NounsWithCase {
+                        nouns: vec![kernumesaxm_d("snelyo")],
+                        case: Case::Preposition(Preposition::El),
+                        span: Span::default(),
+                    },
+                ],
+                mea_clause: Some(vec![mea_elem("dejix", vec![noun_from_ident("deln")])]),
+                span: Span::default(),
+            }],
+            span: Span::default(),
+        },
+    ]
+}
+
+pub fn test_program() {
+    let sentences = parse_program(include_str!("../selsurle.jmk4")).unwrap();
+    assert_eq!(sentences.without_spans(), selsurle_sentences());
 }
 
 #[test]
@@ -242,6 +1075,16 @@ fn parsing_var_decl() {
     test_var_decl();
 }
 
+#[test]
+fn parsing_cond() {
+    test_cond();
+}
+
+#[test]
+fn parsing_predicate_decl() {
+    test_predicate_decl();
+}
+
 #[test]
 fn parsing_import() {
     test_import();
@@ -251,3 +1094,48 @@ fn parsing_import() {
 fn parsing_procedure() {
     test_procedure();
 }
+
+#[test]
+fn parsing_sentence_dispatch() {
+    test_sentence_dispatch();
+}
+
+#[test]
+fn parsing_program() {
+    test_program();
+}
+
+#[test]
+fn parsing_event_handler() {
+    test_event_handler();
+}
+
+#[test]
+fn parsing_event_handler_without_event_cond() {
+    test_event_handler_without_event_cond();
+}
+
+#[test]
+fn parsing_guarded_event_handler() {
+    test_guarded_event_handler();
+}
+
+#[test]
+fn parsing_sentences_in_source_order() {
+    test_sentences_in_source_order();
+}
+
+#[test]
+fn parsing_spans() {
+    test_spans();
+}
+
+#[test]
+fn parsing_parse_error_spans() {
+    test_parse_error_spans();
+}
+
+#[test]
+fn parsing_with_recovery() {
+    test_recovery();
+}
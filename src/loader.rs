@@ -0,0 +1,420 @@
+//! Loading programs split across files.
+//!
+//! `lus a'd b'd x` imports `x` from the file `a/b.jmk4`, looked up in each directory of the
+//! search path in turn. A module exports the names it declares with `es`, and the importing
+//! file sees only the names listed in its `lus`. The module `jmk4` is provided by the runtime
+//! and never looked up.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    parse_program,
+    parser::{Import, Sentence},
+    resolve::{resolve, Definition, DefinitionKind},
+    span::Span,
+    Error,
+};
+
+/// The module provided by the runtime.
+pub const BUILTIN_MODULE: &str = "jmk4";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ImportErrorKind {
+    // `module` is spelled as in the source, e.g. `a'd b`
+    NotFound {
+        module: String,
+        searched: Vec<PathBuf>,
+    },
+    NotExported {
+        module: String,
+        name: String,
+    },
+    // the files in the order they import each other, ending with the first one again
+    Cycle(Vec<PathBuf>),
+}
+
+/// An import that cannot be satisfied. The span is that of the `lus` sentence.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ImportError {
+    pub kind: ImportErrorKind,
+    pub span: Span,
+}
+
+impl ImportError {
+    /// A stable identifier of the kind of error, independent of the message wording.
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self.kind {
+            ImportErrorKind::NotFound { .. } => "E0301",
+            ImportErrorKind::NotExported { .. } => "E0302",
+            ImportErrorKind::Cycle(_) => "E0303",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    Io { file: PathBuf, kind: io::ErrorKind },
+    // an error in the source of `file`, to be rendered against it
+    Source { file: PathBuf, error: Error },
+}
+
+impl LoadError {
+    #[must_use]
+    pub fn file(&self) -> &Path {
+        match self {
+            Self::Io { file, .. } | Self::Source { file, .. } => file,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedModule {
+    // as found on the search path, or as given for the entry file
+    pub path: PathBuf,
+    pub sentences: Vec<Sentence>,
+    pub exports: Vec<Definition>,
+}
+
+impl LoadedModule {
+    #[must_use]
+    pub fn export(&self, name: &str) -> Option<&Definition> {
+        self.exports
+            .iter()
+            .find(|definition| definition.name == name)
+    }
+}
+
+/// Loads a file together with every module it imports, each file once.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleLoader {
+    search_path: Vec<PathBuf>,
+    modules: Vec<LoadedModule>,
+    // index into `modules` by canonical path
+    loaded: HashMap<PathBuf, usize>,
+}
+
+impl ModuleLoader {
+    #[must_use]
+    pub fn new<P: Into<PathBuf>>(search_path: impl IntoIterator<Item = P>) -> Self {
+        Self {
+            search_path: search_path.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Loads `path` and, recursively, the modules it imports.
+    ///
+    /// # Errors
+    /// Returns the first file that cannot be read, parsed or have its imports satisfied.
+    #[allow(clippy::result_large_err)]
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<&LoadedModule, LoadError> {
+        let i = self.load_file(path.as_ref().to_path_buf(), &mut vec![])?;
+        Ok(&self.modules[i])
+    }
+
+    /// Every module loaded so far, each after the modules it imports.
+    #[must_use]
+    pub fn modules(&self) -> &[LoadedModule] {
+        &self.modules
+    }
+
+    /// The loaded module `lus` would import for `module_path`.
+    #[must_use]
+    pub fn module(&self, module_path: &[&str]) -> Option<&LoadedModule> {
+        let (path, _) = self.find(module_path).ok()?;
+        self.loaded.get(&path).map(|&i| &self.modules[i])
+    }
+
+    // `stack` holds the (canonical, as found) paths of the files being loaded
+    #[allow(clippy::result_large_err)]
+    fn load_file(
+        &mut self,
+        path: PathBuf,
+        stack: &mut Vec<(PathBuf, PathBuf)>,
+    ) -> Result<usize, LoadError> {
+        let io_error = |err: io::Error| LoadError::Io {
+            file: path.clone(),
+            kind: err.kind(),
+        };
+        let canonical = fs::canonicalize(&path).map_err(io_error)?;
+        if let Some(&i) = self.loaded.get(&canonical) {
+            return Ok(i);
+        }
+        let source = fs::read_to_string(&path).map_err(io_error)?;
+        let sentences = parse_program(&source).map_err(|error| LoadError::Source {
+            file: path.clone(),
+            error,
+        })?;
+
+        stack.push((canonical.clone(), path.clone()));
+        for sentence in &sentences {
+            if let Sentence::Import(import) = sentence {
+                self.load_import(&path, import, stack)?;
+            }
+        }
+        stack.pop();
+
+        let exports = resolve(&sentences)
+            .0
+            .definitions
+            .into_iter()
+            .filter(|definition| definition.kind == DefinitionKind::Variable)
+            .collect();
+        self.modules.push(LoadedModule {
+            path,
+            sentences,
+            exports,
+        });
+        self.loaded.insert(canonical, self.modules.len() - 1);
+        Ok(self.modules.len() - 1)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn load_import(
+        &mut self,
+        file: &Path,
+        import: &Import,
+        stack: &mut Vec<(PathBuf, PathBuf)>,
+    ) -> Result<(), LoadError> {
+        let module_path = import
+            .module_path
+            .iter()
+            .map(|module| module.0.as_str())
+            .collect::<Vec<_>>();
+        if module_path == [BUILTIN_MODULE] {
+            return Ok(());
+        }
+        let module = module_path.join("'d ");
+        let error = |kind| LoadError::Source {
+            file: file.to_path_buf(),
+            error: Error::Import(ImportError {
+                kind,
+                span: import.span,
+            }),
+        };
+
+        let (canonical, path) = self.find(&module_path).map_err(|searched| {
+            error(ImportErrorKind::NotFound {
+                module: module.clone(),
+                searched,
+            })
+        })?;
+        if let Some(start) = stack.iter().position(|(c, _)| *c == canonical) {
+            let mut chain = stack[start..]
+                .iter()
+                .map(|(_, path)| path.clone())
+                .collect::<Vec<_>>();
+            chain.push(path);
+            return Err(error(ImportErrorKind::Cycle(chain)));
+        }
+        let i = self.load_file(path, stack)?;
+        for name in &import.idents {
+            if self.modules[i].export(name).is_none() {
+                return Err(error(ImportErrorKind::NotExported {
+                    module,
+                    name: name.to_string(),
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    // The (canonical, as found) path of the first file for `module_path` on the search path,
+    // or the paths tried
+    fn find(&self, module_path: &[&str]) -> Result<(PathBuf, PathBuf), Vec<PathBuf>> {
+        let mut relative = module_path.iter().collect::<PathBuf>();
+        relative.set_extension("jmk4");
+        let candidates = self
+            .search_path
+            .iter()
+            .map(|dir| dir.join(&relative))
+            .collect::<Vec<_>>();
+        candidates
+            .iter()
+            .find_map(|path| {
+                let canonical = fs::canonicalize(path).ok()?;
+                canonical.is_file().then(|| (canonical, path.clone()))
+            })
+            .ok_or(candidates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{messages::Locale, parser::ParseError};
+
+    // a scratch directory, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir().join(format!("jmk4-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            for (file, source) in files {
+                let path = dir.join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, source).unwrap();
+            }
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn load() {
+        let dir = TempDir::new(
+            "load",
+            &[
+                (
+                    "app/main.jmk4",
+                    "lus jmk4'd jerldir. lus ui'd sel'd selsurle ad iulo. lus nert'd ektir. is selsurle iulo'c.",
+                ),
+                ("lib/ui/sel.jmk4", "lus nert'd ektir. selsurle es ektir. iulo es 0."),
+                ("app/nert.jmk4", "ektir es 1."),
+                ("lib/nert.jmk4", "ektir es 2."),
+            ],
+        );
+        let mut loader = ModuleLoader::new([dir.0.join("app"), dir.0.join("lib")]);
+        let main = loader.load(dir.0.join("app/main.jmk4")).unwrap();
+        assert_eq!(main.sentences.len(), 4);
+        assert_eq!(main.exports, vec![]);
+        let modules = loader
+            .modules()
+            .iter()
+            .map(|module| module.path.strip_prefix(&dir.0).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            modules,
+            ["app/nert.jmk4", "lib/ui/sel.jmk4", "app/main.jmk4"].map(PathBuf::from)
+        );
+        let sel = loader.module(&["ui", "sel"]).unwrap();
+        assert_eq!(sel.export("selsurle").unwrap().span.range(), 18..26);
+        assert_eq!(sel.export("ektir"), None);
+    }
+
+    #[test]
+    fn not_found() {
+        let dir = TempDir::new(
+            "not-found",
+            &[("main.jmk4", "selsurle es iu.\nlus ui'd sel'd selsurle.")],
+        );
+        let mut loader = ModuleLoader::new([&dir.0]);
+        let err = loader.load(dir.0.join("main.jmk4")).unwrap_err();
+        let LoadError::Source {
+            error: Error::Import(err),
+            ..
+        } = err
+        else {
+            panic!("{err:?}");
+        };
+        assert_eq!(
+            err.kind,
+            ImportErrorKind::NotFound {
+                module: "ui'd sel".to_string(),
+                searched: vec![dir.0.join("ui/sel.jmk4")],
+            }
+        );
+        assert_eq!((err.code(), err.span.range()), ("E0301", 16..39));
+    }
+
+    #[test]
+    fn not_exported() {
+        let dir = TempDir::new(
+            "not-exported",
+            &[
+                ("main.jmk4", "lus nert'd ektir ad deln."),
+                ("nert.jmk4", "ektir es 1."),
+            ],
+        );
+        let err = ModuleLoader::new([&dir.0])
+            .load(dir.0.join("main.jmk4"))
+            .unwrap_err();
+        assert_eq!(err.file(), dir.0.join("main.jmk4"));
+        assert!(matches!(
+            err,
+            LoadError::Source {
+                error: Error::Import(ImportError {
+                    kind: ImportErrorKind::NotExported { ref module, ref name },
+                    ..
+                }),
+                ..
+            } if module == "nert" && name == "deln"
+        ));
+    }
+
+    #[test]
+    fn cycle() {
+        let dir = TempDir::new(
+            "cycle",
+            &[
+                ("main.jmk4", "lus a'd x."),
+                ("a.jmk4", "lus b'd y. x es 1."),
+                ("b.jmk4", "lus a'd x. y es 2."),
+            ],
+        );
+        let err = ModuleLoader::new([&dir.0])
+            .load(dir.0.join("main.jmk4"))
+            .unwrap_err();
+        assert_eq!(err.file(), dir.0.join("b.jmk4"));
+        let LoadError::Source {
+            error: Error::Import(err),
+            ..
+        } = err
+        else {
+            panic!("{err:?}");
+        };
+        assert_eq!(
+            err.kind,
+            ImportErrorKind::Cycle(
+                ["a.jmk4", "b.jmk4", "a.jmk4"]
+                    .map(|f| dir.0.join(f))
+                    .to_vec()
+            )
+        );
+        let dir = dir.0.display();
+        assert_eq!(
+            Locale::English.import_error_note(&err.kind),
+            format!("{dir}/a.jmk4 → {dir}/b.jmk4 → {dir}/a.jmk4")
+        );
+    }
+
+    #[test]
+    fn errors_in_imported_files() {
+        let dir = TempDir::new(
+            "errors",
+            &[("main.jmk4", "lus a'd x."), ("a.jmk4", "x es .")],
+        );
+        let err = ModuleLoader::new([&dir.0])
+            .load(dir.0.join("main.jmk4"))
+            .unwrap_err();
+        assert_eq!(err.file(), dir.0.join("a.jmk4"));
+        assert!(matches!(
+            err,
+            LoadError::Source {
+                error: Error::Parse(ParseError::UnexpectedToken { .. }),
+                ..
+            }
+        ));
+        let err = ModuleLoader::new([&dir.0])
+            .load(dir.0.join("missing.jmk4"))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::Io {
+                kind: io::ErrorKind::NotFound,
+                ..
+            }
+        ));
+    }
+}
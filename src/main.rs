@@ -0,0 +1,75 @@
+#![warn(clippy::pedantic, clippy::nursery)]
+
+use std::{
+    env, fs,
+    io::{self, Read},
+    process::ExitCode,
+};
+
+use jmk4::{diagnostics::Diagnostic, format_cst, Cst, Error};
+
+const USAGE: &str = "usage: jmk4 fmt [FILE]...
+  Rewrites each FILE in canonical form; with no FILE, formats stdin to stdout.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.split_first() {
+        Some((command, files)) if command == "fmt" => fmt(files),
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn fmt(files: &[String]) -> ExitCode {
+    if files.is_empty() {
+        let mut source = String::new();
+        if let Err(err) = io::stdin().read_to_string(&mut source) {
+            eprintln!("jmk4: <stdin>: {err}");
+            return ExitCode::FAILURE;
+        }
+        let Some(formatted) = format_source("<stdin>", &source) else {
+            return ExitCode::FAILURE;
+        };
+        print!("{formatted}");
+        return ExitCode::SUCCESS;
+    }
+
+    let mut status = ExitCode::SUCCESS;
+    for file in files {
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("jmk4: {file}: {err}");
+                status = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        let Some(formatted) = format_source(file, &source) else {
+            status = ExitCode::FAILURE;
+            continue;
+        };
+        if formatted != source {
+            if let Err(err) = fs::write(file, formatted) {
+                eprintln!("jmk4: {file}: {err}");
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    status
+}
+
+fn format_source(file_name: &str, source: &str) -> Option<String> {
+    let formatted = match Cst::parse(source) {
+        Ok(cst) => format_cst(&cst).map_err(Error::from),
+        Err(err) => Err(Error::from(err)),
+    };
+    match formatted {
+        Ok(formatted) => Some(formatted),
+        Err(err) => {
+            eprint!("{}", Diagnostic::from(&err).render(file_name, source));
+            None
+        }
+    }
+}
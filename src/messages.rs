@@ -0,0 +1,520 @@
+use crate::{
+    loader::ImportErrorKind,
+    parser::{Expected, Rule},
+    predicate::PredicateErrorKind,
+    resolve::ResolveErrorKind,
+    signature::{Arity, SignatureErrorKind},
+    token::TokenKind,
+    tokenize::LexErrorKind,
+    types::TypeErrorKind,
+};
+
+/// The language diagnostics are written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Locale {
+    #[default]
+    Japanese,
+    English,
+}
+
+// The message catalogue. Every user-facing sentence of the lexer, the parser and the later
+// checks lives here.
+impl Locale {
+    #[must_use]
+    pub const fn error_heading(self) -> &'static str {
+        match self {
+            Self::Japanese => "エラー",
+            Self::English => "error",
+        }
+    }
+
+    #[must_use]
+    pub const fn expected_heading(self) -> &'static str {
+        match self {
+            Self::Japanese => "期待されるもの",
+            Self::English => "expected",
+        }
+    }
+
+    #[must_use]
+    pub const fn note_heading(self) -> &'static str {
+        match self {
+            Self::Japanese => "注",
+            Self::English => "note",
+        }
+    }
+
+    #[must_use]
+    pub fn expected(self, expected: Expected) -> String {
+        match (self, expected) {
+            (Self::Japanese, Expected::Ident) => "（識別子）".to_string(),
+            (Self::Japanese, Expected::StringLiteral) => "（文字列リテラル）".to_string(),
+            (Self::English, Expected::Ident) => "an identifier".to_string(),
+            (Self::Japanese, Expected::Number) => "（数値）".to_string(),
+            (Self::English, Expected::StringLiteral) => "a string literal".to_string(),
+            (Self::English, Expected::Number) => "a number".to_string(),
+            (_, Expected::Reserved(reserved)) => reserved.as_str().to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn lex_error_message(self, kind: &LexErrorKind) -> String {
+        match (self, kind) {
+            (Self::Japanese, LexErrorKind::IllegalCharacter(c)) => {
+                format!("使えない文字 `{c}` です")
+            }
+            (Self::English, LexErrorKind::IllegalCharacter(c)) => {
+                format!("illegal character `{c}`")
+            }
+            (Self::Japanese, LexErrorKind::UnmatchedClosingBracket) => {
+                "対応する `<` のない `>` です".to_string()
+            }
+            (Self::English, LexErrorKind::UnmatchedClosingBracket) => {
+                "`>` without a matching `<`".to_string()
+            }
+            (Self::Japanese, LexErrorKind::UnterminatedStringLiteral) => {
+                "文字列リテラルが閉じられていません".to_string()
+            }
+            (Self::English, LexErrorKind::UnterminatedStringLiteral) => {
+                "unterminated string literal".to_string()
+            }
+            (Self::Japanese, LexErrorKind::UnterminatedBlockComment) => {
+                "ブロックコメントが閉じられていません".to_string()
+            }
+            (Self::English, LexErrorKind::UnterminatedBlockComment) => {
+                "unterminated block comment".to_string()
+            }
+            (Self::Japanese, LexErrorKind::NumberOutOfRange) => "数値が大きすぎます".to_string(),
+            (Self::English, LexErrorKind::NumberOutOfRange) => "number out of range".to_string(),
+            (Self::Japanese, LexErrorKind::InvalidEscape) => {
+                "不正なエスケープシーケンスです".to_string()
+            }
+            (Self::English, LexErrorKind::InvalidEscape) => "invalid escape sequence".to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn lex_error_label(self, kind: &LexErrorKind) -> String {
+        match (self, kind) {
+            (Self::Japanese, LexErrorKind::IllegalCharacter(_)) => "この文字".to_string(),
+            (Self::English, LexErrorKind::IllegalCharacter(_)) => "this character".to_string(),
+            (Self::Japanese, LexErrorKind::UnmatchedClosingBracket) => "この `>`".to_string(),
+            (Self::English, LexErrorKind::UnmatchedClosingBracket) => "this `>`".to_string(),
+            (Self::Japanese, LexErrorKind::UnterminatedStringLiteral) => {
+                "ここから始まる文字列リテラル".to_string()
+            }
+            (Self::English, LexErrorKind::UnterminatedStringLiteral) => {
+                "the string literal starting here".to_string()
+            }
+            (Self::Japanese, LexErrorKind::UnterminatedBlockComment) => {
+                "ここから始まるブロックコメント".to_string()
+            }
+            (Self::English, LexErrorKind::UnterminatedBlockComment) => {
+                "the block comment starting here".to_string()
+            }
+            (Self::Japanese, LexErrorKind::NumberOutOfRange) => "この数値".to_string(),
+            (Self::English, LexErrorKind::NumberOutOfRange) => "this number".to_string(),
+            (Self::Japanese, LexErrorKind::InvalidEscape) => "このエスケープ".to_string(),
+            (Self::English, LexErrorKind::InvalidEscape) => "this escape".to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn lex_error_note(self, kind: &LexErrorKind) -> Option<String> {
+        match (self, kind) {
+            (Self::Japanese, LexErrorKind::IllegalCharacter(_)) => {
+                Some("`<` と `>` で囲んだ文字列リテラルの中ではどの文字でも使えます".to_string())
+            }
+            (Self::English, LexErrorKind::IllegalCharacter(_)) => Some(
+                "any character can be used inside a string literal enclosed in `<` and `>`"
+                    .to_string(),
+            ),
+            (_, LexErrorKind::UnmatchedClosingBracket) => None,
+            (Self::Japanese, LexErrorKind::UnterminatedStringLiteral) => {
+                Some("文字列リテラルは `>` で閉じる必要があります".to_string())
+            }
+            (Self::English, LexErrorKind::UnterminatedStringLiteral) => {
+                Some("a string literal must be closed with `>`".to_string())
+            }
+            (Self::Japanese, LexErrorKind::UnterminatedBlockComment) => {
+                Some("ブロックコメントは `}#` で閉じる必要があります".to_string())
+            }
+            (Self::English, LexErrorKind::UnterminatedBlockComment) => {
+                Some("a block comment must be closed with `}#`".to_string())
+            }
+            (Self::Japanese, LexErrorKind::NumberOutOfRange) => Some(
+                "小数点を取り除いた数字の並びが 64 ビット符号付き整数に収まる必要があります"
+                    .to_string(),
+            ),
+            (Self::English, LexErrorKind::NumberOutOfRange) => Some(
+                "the digits, without the decimal point, must fit in a 64-bit signed integer"
+                    .to_string(),
+            ),
+            (Self::Japanese, LexErrorKind::InvalidEscape) => Some(
+                "使えるエスケープは `\\<` `\\>` `\\\\` `\\n` `\\t` `\\u{…}` と行末の `\\` です"
+                    .to_string(),
+            ),
+            (Self::English, LexErrorKind::InvalidEscape) => Some(
+                "the escapes are `\\<`, `\\>`, `\\\\`, `\\n`, `\\t`, `\\u{…}` and `\\` at the end of a line"
+                    .to_string(),
+            ),
+        }
+    }
+
+    #[must_use]
+    pub fn unexpected_token(self, actual: &TokenKind) -> String {
+        match self {
+            Self::Japanese => format!("予期しないトークン `{actual}` です"),
+            Self::English => format!("unexpected token `{actual}`"),
+        }
+    }
+
+    #[must_use]
+    pub fn unexpected_token_label(self, rule: Rule) -> String {
+        match self {
+            Self::Japanese => format!("`{rule}` の途中にこのトークンは置けません"),
+            Self::English => format!("this token cannot appear in `{rule}`"),
+        }
+    }
+
+    #[must_use]
+    pub fn end_of_file(self) -> String {
+        match self {
+            Self::Japanese => "予期しないファイル終端です".to_string(),
+            Self::English => "unexpected end of file".to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn end_of_file_label(self, rule: Rule) -> String {
+        match self {
+            Self::Japanese => format!("`{rule}` の途中で入力が終わっています"),
+            Self::English => format!("the input ends in the middle of `{rule}`"),
+        }
+    }
+
+    #[must_use]
+    pub fn end_of_file_note(self) -> String {
+        match self {
+            Self::Japanese => "文は `.` で終わる必要があります".to_string(),
+            Self::English => "a sentence must end with `.`".to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn resolve_error_message(self, kind: &ResolveErrorKind) -> String {
+        match (self, kind) {
+            (Self::Japanese, ResolveErrorKind::Undefined(name)) => {
+                format!("`{name}` は定義されていません")
+            }
+            (Self::English, ResolveErrorKind::Undefined(name)) => {
+                format!("undefined name `{name}`")
+            }
+            (Self::Japanese, ResolveErrorKind::Duplicate { name, .. }) => {
+                format!("`{name}` が二度定義されています")
+            }
+            (Self::English, ResolveErrorKind::Duplicate { name, .. }) => {
+                format!("`{name}` is defined more than once")
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn resolve_error_label(self, kind: &ResolveErrorKind) -> String {
+        match (self, kind) {
+            (Self::Japanese, ResolveErrorKind::Undefined(_)) => "この名前".to_string(),
+            (Self::English, ResolveErrorKind::Undefined(_)) => "this name".to_string(),
+            (Self::Japanese, ResolveErrorKind::Duplicate { .. }) => "二度目の定義".to_string(),
+            (Self::English, ResolveErrorKind::Duplicate { .. }) => "defined again here".to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn resolve_error_note(self, kind: &ResolveErrorKind) -> String {
+        match (self, kind) {
+            (Self::Japanese, ResolveErrorKind::Undefined(_)) => {
+                "名前は `lus` で取り込むか `es` で宣言する必要があります".to_string()
+            }
+            (Self::English, ResolveErrorKind::Undefined(_)) => {
+                "a name must be imported with `lus` or declared with `es`".to_string()
+            }
+            (Self::Japanese, ResolveErrorKind::Duplicate { previous, .. }) => {
+                format!("最初の定義は {} 行目です", previous.start.line)
+            }
+            (Self::English, ResolveErrorKind::Duplicate { previous, .. }) => {
+                format!("first defined on line {}", previous.start.line)
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn import_error_message(self, kind: &ImportErrorKind) -> String {
+        match (self, kind) {
+            (Self::Japanese, ImportErrorKind::NotFound { module, .. }) => {
+                format!("モジュール `{module}` が見つかりません")
+            }
+            (Self::English, ImportErrorKind::NotFound { module, .. }) => {
+                format!("module `{module}` not found")
+            }
+            (Self::Japanese, ImportErrorKind::NotExported { module, name }) => {
+                format!("モジュール `{module}` は `{name}` を定義していません")
+            }
+            (Self::English, ImportErrorKind::NotExported { module, name }) => {
+                format!("module `{module}` does not define `{name}`")
+            }
+            (Self::Japanese, ImportErrorKind::Cycle(_)) => {
+                "モジュールの取り込みが循環しています".to_string()
+            }
+            (Self::English, ImportErrorKind::Cycle(_)) => "import cycle".to_string(),
+        }
+    }
+
+    #[must_use]
+    pub const fn import_error_label(self) -> &'static str {
+        match self {
+            Self::Japanese => "この取り込み",
+            Self::English => "imported here",
+        }
+    }
+
+    #[must_use]
+    pub fn import_error_note(self, kind: &ImportErrorKind) -> String {
+        let paths = |paths: &[std::path::PathBuf], separator| {
+            paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(separator)
+        };
+        match (self, kind) {
+            (Self::Japanese, ImportErrorKind::NotFound { searched, .. }) => {
+                format!("探した場所: {}", paths(searched, ", "))
+            }
+            (Self::English, ImportErrorKind::NotFound { searched, .. }) => {
+                format!("searched {}", paths(searched, ", "))
+            }
+            (Self::Japanese, ImportErrorKind::NotExported { .. }) => {
+                "モジュールから取り込めるのは `es` で宣言された名前だけです".to_string()
+            }
+            (Self::English, ImportErrorKind::NotExported { .. }) => {
+                "only names declared with `es` can be imported from a module".to_string()
+            }
+            (_, ImportErrorKind::Cycle(chain)) => paths(chain, " → "),
+        }
+    }
+
+    #[must_use]
+    pub fn signature_error_message(self, kind: &SignatureErrorKind) -> String {
+        use SignatureErrorKind as K;
+        match (self, kind) {
+            (Self::Japanese, K::UnknownVerb(verb)) => {
+                format!("動詞 `{verb}` は登録されていません")
+            }
+            (Self::English, K::UnknownVerb(verb)) => format!("unknown verb `{verb}`"),
+            (Self::Japanese, K::CaseNotAllowed { verb, case }) => {
+                format!("`{verb}` は `{}` を取りません", case.as_str())
+            }
+            (Self::English, K::CaseNotAllowed { verb, case }) => {
+                format!("`{verb}` does not take `{}`", case.as_str())
+            }
+            (Self::Japanese, K::DuplicateCase { case, .. }) => {
+                format!("`{}` が二度使われています", case.as_str())
+            }
+            (Self::English, K::DuplicateCase { case, .. }) => {
+                format!("`{}` is used twice", case.as_str())
+            }
+            (
+                Self::Japanese,
+                K::Arity {
+                    verb,
+                    case,
+                    expected,
+                    found,
+                },
+            ) => format!(
+                "`{verb}` の `{}` には名詞が{}必要ですが {found} 個あります",
+                case.as_str(),
+                self.arity(*expected)
+            ),
+            (
+                Self::English,
+                K::Arity {
+                    verb,
+                    case,
+                    expected,
+                    found,
+                },
+            ) => format!(
+                "`{}` of `{verb}` takes {}, found {found}",
+                case.as_str(),
+                self.arity(*expected)
+            ),
+            (Self::Japanese, K::MissingCase { verb, case }) => {
+                format!("`{verb}` には `{}` が必要です", case.as_str())
+            }
+            (Self::English, K::MissingCase { verb, case }) => {
+                format!("`{verb}` requires `{}`", case.as_str())
+            }
+            (Self::Japanese, K::NamedParameterNotAllowed { verb, name }) => {
+                format!("`{verb}` に `{name}` という引数はありません")
+            }
+            (Self::English, K::NamedParameterNotAllowed { verb, name }) => {
+                format!("`{verb}` has no parameter `{name}`")
+            }
+        }
+    }
+
+    #[must_use]
+    pub const fn signature_error_label(self, kind: &SignatureErrorKind) -> &'static str {
+        use SignatureErrorKind as K;
+        match (self, kind) {
+            (Self::Japanese, K::UnknownVerb(_)) => "この動詞",
+            (Self::English, K::UnknownVerb(_)) => "this verb",
+            (Self::Japanese, K::CaseNotAllowed { .. }) => "この格",
+            (Self::English, K::CaseNotAllowed { .. }) => "this case",
+            (Self::Japanese, K::DuplicateCase { .. }) => "二度目",
+            (Self::English, K::DuplicateCase { .. }) => "used again here",
+            (Self::Japanese, K::Arity { .. }) => "この名詞の並び",
+            (Self::English, K::Arity { .. }) => "these nouns",
+            (Self::Japanese, K::MissingCase { .. }) => "この手続き",
+            (Self::English, K::MissingCase { .. }) => "this procedure",
+            (Self::Japanese, K::NamedParameterNotAllowed { .. }) => "この引数",
+            (Self::English, K::NamedParameterNotAllowed { .. }) => "this parameter",
+        }
+    }
+
+    // the number of nouns a case takes, e.g. " 2 個" or "2 nouns"
+    fn arity(self, arity: Arity) -> String {
+        let plural = |n: usize| if n == 1 { "noun" } else { "nouns" };
+        match (self, arity.max) {
+            (Self::Japanese, Some(max)) if max == arity.min => format!(" {max} 個"),
+            (Self::Japanese, Some(max)) => format!(" {}〜{max} 個", arity.min),
+            (Self::Japanese, None) => format!(" {} 個以上", arity.min),
+            (Self::English, Some(max)) if max == arity.min => format!("{max} {}", plural(max)),
+            (Self::English, Some(max)) => format!("{} to {max} nouns", arity.min),
+            (Self::English, None) => format!("at least {} {}", arity.min, plural(arity.min)),
+        }
+    }
+
+    #[must_use]
+    pub fn type_error_message(self, kind: &TypeErrorKind) -> String {
+        match (self, kind) {
+            (Self::Japanese, TypeErrorKind::Mismatch { expected, found }) => {
+                format!("型が合いません: `{expected}` が必要ですが `{found}` です")
+            }
+            (Self::English, TypeErrorKind::Mismatch { expected, found }) => {
+                format!("mismatched types: expected `{expected}`, found `{found}`")
+            }
+            (Self::Japanese, TypeErrorKind::UnknownProperty { owner, name }) => {
+                format!("`{owner}` に `{name}` という属性はありません")
+            }
+            (Self::English, TypeErrorKind::UnknownProperty { owner, name }) => {
+                format!("`{owner}` has no property `{name}`")
+            }
+        }
+    }
+
+    #[must_use]
+    pub const fn type_error_label(self, kind: &TypeErrorKind) -> &'static str {
+        match (self, kind) {
+            (Self::Japanese, TypeErrorKind::Mismatch { .. }) => "この名詞",
+            (Self::English, TypeErrorKind::Mismatch { .. }) => "this noun",
+            (Self::Japanese, TypeErrorKind::UnknownProperty { .. }) => "この属性",
+            (Self::English, TypeErrorKind::UnknownProperty { .. }) => "this property",
+        }
+    }
+
+    #[must_use]
+    pub fn predicate_error_message(self, kind: &PredicateErrorKind) -> String {
+        use PredicateErrorKind as K;
+        match (self, kind) {
+            (Self::Japanese, K::UnusedParameter(name)) => {
+                format!("引数 `{name}` が使われていません")
+            }
+            (Self::English, K::UnusedParameter(name)) => format!("unused parameter `{name}`"),
+            (Self::Japanese, K::UnboundName(name)) => {
+                format!("`{name}` は述語の引数ではありません")
+            }
+            (Self::English, K::UnboundName(name)) => {
+                format!("`{name}` is not a parameter of the predicate")
+            }
+            (
+                Self::Japanese,
+                K::Arity {
+                    predicate,
+                    expected,
+                    found,
+                },
+            ) => format!("述語 `{predicate}` の引数は {expected} 個ですが {found} 個あります"),
+            (
+                Self::English,
+                K::Arity {
+                    predicate,
+                    expected,
+                    found,
+                },
+            ) => format!(
+                "predicate `{predicate}` takes {expected} {}, found {found}",
+                if *expected == 1 {
+                    "argument"
+                } else {
+                    "arguments"
+                }
+            ),
+            (Self::Japanese, K::Recursive(chain)) => {
+                format!("述語 `{}` が再帰しています", chain[0])
+            }
+            (Self::English, K::Recursive(chain)) => {
+                format!("predicate `{}` is recursive", chain[0])
+            }
+            (Self::Japanese, K::Duplicate { predicate, .. }) => {
+                format!("述語 `{predicate}` が二度宣言されています")
+            }
+            (Self::English, K::Duplicate { predicate, .. }) => {
+                format!("predicate `{predicate}` is declared twice")
+            }
+        }
+    }
+
+    #[must_use]
+    pub const fn predicate_error_label(self, kind: &PredicateErrorKind) -> &'static str {
+        use PredicateErrorKind as K;
+        match (self, kind) {
+            (Self::Japanese, K::UnusedParameter(_)) => "この引数",
+            (Self::English, K::UnusedParameter(_)) => "this parameter",
+            (Self::Japanese, K::UnboundName(_)) => "この名前",
+            (Self::English, K::UnboundName(_)) => "this name",
+            (Self::Japanese, K::Arity { .. }) => "この条件",
+            (Self::English, K::Arity { .. }) => "this condition",
+            (Self::Japanese, K::Recursive(_)) => "この述語",
+            (Self::English, K::Recursive(_)) => "this predicate",
+            (Self::Japanese, K::Duplicate { .. }) => "二度目の宣言",
+            (Self::English, K::Duplicate { .. }) => "declared again here",
+        }
+    }
+
+    #[must_use]
+    pub fn predicate_error_note(self, kind: &PredicateErrorKind) -> Option<String> {
+        use PredicateErrorKind as K;
+        match (self, kind) {
+            (Self::Japanese, K::UnboundName(_)) => {
+                Some("述語の条件で使えるのは引数だけです".to_string())
+            }
+            (Self::English, K::UnboundName(_)) => {
+                Some("the condition of a predicate may only use its parameters".to_string())
+            }
+            (Self::Japanese, K::Arity { .. }) => Some("引数は主語と格の付いた名詞です".to_string()),
+            (Self::English, K::Arity { .. }) => {
+                Some("the arguments are the subject and the nouns with case".to_string())
+            }
+            (_, K::Recursive(chain)) => Some(chain.join(" → ")),
+            (Self::Japanese, K::Duplicate { previous, .. }) => {
+                Some(format!("最初の宣言は {} 行目です", previous.start.line))
+            }
+            (Self::English, K::Duplicate { previous, .. }) => {
+                Some(format!("first declared on line {}", previous.start.line))
+            }
+            (_, K::UnusedParameter(_)) => None,
+        }
+    }
+}
@@ -1,81 +1,207 @@
-use crate::token::{Preposition, Reserved, Token};
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::messages::Locale;
+use crate::span::{Span, WithoutSpans};
+use crate::symbol::Symbol;
+use crate::token::{Number, Preposition, Reserved, Token, TokenKind};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum PrimaryNoun {
-    StringLiteral { literal: String },
-    Ident { ident: String },
+    // `literal` is the source text with its `<` and `>`, `value` the decoded contents
+    StringLiteral {
+        literal: Symbol,
+        value: Symbol,
+        span: Span,
+    },
+    Number {
+        number: Number,
+        span: Span,
+    },
+    Ident {
+        ident: Symbol,
+        span: Span,
+    },
+}
+
+impl PrimaryNoun {
+    #[must_use]
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::StringLiteral { span, .. }
+            | Self::Ident { span, .. }
+            | Self::Number { span, .. } => *span,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Verb(pub String);
+pub struct Verb(pub Symbol, pub Span);
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Module(pub String);
+pub struct Module(pub Symbol, pub Span);
+
+/// Identifies a noun for the analyses, which key what they find by it.
+///
+/// Nouns spelled the same in the same place, e.g. in two files, get different ids: the parser
+/// gives every noun its own, unique in the process. `NounId::default()` is left for nouns
+/// built by hand.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct NounId(u64);
+
+impl NounId {
+    #[must_use]
+    pub fn fresh() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Noun {
     pub modifier: Vec<PrimaryNoun>,
     pub head: PrimaryNoun,
+    pub span: Span,
+    pub id: NounId,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Case {
     Preposition(Preposition),
     ApostropheC,
+    // instrumental: what the action is done with
+    ApostropheI,
+}
+
+impl Case {
+    /// The preposition or suffix marking the case.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Preposition(p) => p.as_str(),
+            Self::ApostropheC => Reserved::ApostropheC.as_str(),
+            Self::ApostropheI => Reserved::ApostropheI.as_str(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct NounsWithCase {
     pub nouns: Vec<Noun>,
     pub case: Case,
+    pub span: Span,
 }
 
+// sentence = procedure
+//   | import
+//   | var_decl
+//   | predicate_decl
+//   | event_cond* "elx" "shrlo" procedure ("melx" "shrlo" procedure)*
+//   | event_cond* cond "felx" "shrlo" procedure ("melx" "shrlo" procedure)*
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Sentence {
+    Procedure(Procedure),
+    Import(Import),
     VarDecl(Noun, Noun),
     PredicateDecl {
         noun_list: Vec<Noun>,
         verb: Verb,
         cond: Cond,
+        span: Span,
     },
+    EventHandler {
+        event_conds: Vec<EventCond>,
+        procedures: Vec<Procedure>,
+        span: Span,
+    },
+    GuardedEventHandler {
+        event_conds: Vec<EventCond>,
+        guard: Cond,
+        procedures: Vec<Procedure>,
+        span: Span,
+    },
+}
+
+impl Sentence {
+    #[must_use]
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Procedure(Procedure { span, .. })
+            | Self::Import(Import { span, .. })
+            | Self::PredicateDecl { span, .. }
+            | Self::EventHandler { span, .. }
+            | Self::GuardedEventHandler { span, .. } => *span,
+            Self::VarDecl(noun1, noun2) => noun1.span.to(noun2.span),
+        }
+    }
+}
+
+impl From<Import> for Sentence {
+    fn from(import: Import) -> Self {
+        Self::Import(import)
+    }
+}
+
+impl From<Procedure> for Sentence {
+    fn from(procedure: Procedure) -> Self {
+        Self::Procedure(procedure)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct EventCond {
-    noun: Noun,
-    verb: Verb,
+    pub noun: Noun,
+    pub verb: Verb,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CondElem {
     pub noun: Noun,
     pub verb: Verb,
-    pub nouns_with_case: Option<NounsWithCase>,
+    pub nouns_with_case_array: Vec<NounsWithCase>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct NamedParameter {
-    pub modifiers: Vec<String>,
-    pub head: String,
+    pub modifiers: Vec<Symbol>,
+    pub head: Symbol,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MeaElem {
-    named_parameter: NamedParameter,
-    noun_list: Vec<Noun>,
+    pub named_parameter: NamedParameter,
+    pub noun_list: Vec<Noun>,
+    pub span: Span,
 }
 
 // noun verb nouns_with_case* ("mal" noun verb nouns_with_case*)*
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Cond(pub Vec<CondElem>);
 
+impl Cond {
+    #[must_use]
+    pub fn span(&self) -> Span {
+        match self.0.as_slice() {
+            [] => Span::default(),
+            [first, .., last] => first.span.to(last.span),
+            [only] => only.span,
+        }
+    }
+}
+
 // import = "lus" (module "'d")+ (
 //        ident | ident "ad" ident | ident "adit" ident ("," ident)+
 //      )
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Import {
     pub module_path: Vec<Module>,
-    pub idents: Vec<String>,
+    pub idents: Vec<Symbol>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -84,57 +210,405 @@ pub struct Procedure {
     pub noun: Noun,
     pub nouns_with_case_array: Vec<NounsWithCase>,
     pub mea_clause: Option<Vec<MeaElem>>,
+    pub span: Span,
+}
+
+impl WithoutSpans for PrimaryNoun {
+    fn without_spans(&self) -> Self {
+        match self {
+            Self::StringLiteral { literal, value, .. } => Self::StringLiteral {
+                literal: literal.clone(),
+                value: value.clone(),
+                span: Span::default(),
+            },
+            Self::Number { number, .. } => Self::Number {
+                number: *number,
+                span: Span::default(),
+            },
+            Self::Ident { ident, .. } => Self::Ident {
+                ident: ident.clone(),
+                span: Span::default(),
+            },
+        }
+    }
+}
+
+impl WithoutSpans for Verb {
+    fn without_spans(&self) -> Self {
+        Self(self.0.clone(), Span::default())
+    }
+}
+
+impl WithoutSpans for Module {
+    fn without_spans(&self) -> Self {
+        Self(self.0.clone(), Span::default())
+    }
+}
+
+impl WithoutSpans for Noun {
+    fn without_spans(&self) -> Self {
+        Self {
+            modifier: self.modifier.without_spans(),
+            head: self.head.without_spans(),
+            span: Span::default(),
+            id: NounId::default(),
+        }
+    }
+}
+
+impl WithoutSpans for NounsWithCase {
+    fn without_spans(&self) -> Self {
+        Self {
+            nouns: self.nouns.without_spans(),
+            case: self.case,
+            span: Span::default(),
+        }
+    }
+}
+
+impl WithoutSpans for Sentence {
+    fn without_spans(&self) -> Self {
+        match self {
+            Self::Procedure(procedure) => Self::Procedure(procedure.without_spans()),
+            Self::Import(import) => Self::Import(import.without_spans()),
+            Self::VarDecl(noun1, noun2) => {
+                Self::VarDecl(noun1.without_spans(), noun2.without_spans())
+            }
+            Self::PredicateDecl {
+                noun_list,
+                verb,
+                cond,
+                ..
+            } => Self::PredicateDecl {
+                noun_list: noun_list.without_spans(),
+                verb: verb.without_spans(),
+                cond: cond.without_spans(),
+                span: Span::default(),
+            },
+            Self::EventHandler {
+                event_conds,
+                procedures,
+                ..
+            } => Self::EventHandler {
+                event_conds: event_conds.without_spans(),
+                procedures: procedures.without_spans(),
+                span: Span::default(),
+            },
+            Self::GuardedEventHandler {
+                event_conds,
+                guard,
+                procedures,
+                ..
+            } => Self::GuardedEventHandler {
+                event_conds: event_conds.without_spans(),
+                guard: guard.without_spans(),
+                procedures: procedures.without_spans(),
+                span: Span::default(),
+            },
+        }
+    }
+}
+
+impl WithoutSpans for EventCond {
+    fn without_spans(&self) -> Self {
+        Self {
+            noun: self.noun.without_spans(),
+            verb: self.verb.without_spans(),
+            span: Span::default(),
+        }
+    }
+}
+
+impl WithoutSpans for CondElem {
+    fn without_spans(&self) -> Self {
+        Self {
+            noun: self.noun.without_spans(),
+            verb: self.verb.without_spans(),
+            nouns_with_case_array: self.nouns_with_case_array.without_spans(),
+            span: Span::default(),
+        }
+    }
+}
+
+impl WithoutSpans for NamedParameter {
+    fn without_spans(&self) -> Self {
+        Self {
+            modifiers: self.modifiers.clone(),
+            head: self.head.clone(),
+            span: Span::default(),
+        }
+    }
+}
+
+impl WithoutSpans for MeaElem {
+    fn without_spans(&self) -> Self {
+        Self {
+            named_parameter: self.named_parameter.without_spans(),
+            noun_list: self.noun_list.without_spans(),
+            span: Span::default(),
+        }
+    }
+}
+
+impl WithoutSpans for Cond {
+    fn without_spans(&self) -> Self {
+        Self(self.0.without_spans())
+    }
+}
+
+impl WithoutSpans for Import {
+    fn without_spans(&self) -> Self {
+        Self {
+            module_path: self.module_path.without_spans(),
+            idents: self.idents.clone(),
+            span: Span::default(),
+        }
+    }
+}
+
+impl WithoutSpans for Procedure {
+    fn without_spans(&self) -> Self {
+        Self {
+            verb: self.verb.without_spans(),
+            noun: self.noun.without_spans(),
+            nouns_with_case_array: self.nouns_with_case_array.without_spans(),
+            mea_clause: self.mea_clause.without_spans(),
+            span: Span::default(),
+        }
+    }
+}
+
+/// A token the parser would have accepted in place of the one it found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Expected {
+    Ident,
+    StringLiteral,
+    Number,
+    Reserved(Reserved),
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Locale::default().expected(*self))
+    }
+}
+
+/// The grammar rule (as named in `grammar.txt`) that was being parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    PrimaryNoun,
+    Noun,
+    NounList,
+    NounsWithCase,
+    Program,
+    Sentence,
+    Verb,
+    MeaClause,
+    Procedure,
+    Module,
+    Import,
+    VarDecl,
+    PredicateDecl,
+    EventCond,
+    Cond,
+}
+
+impl Rule {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::PrimaryNoun => "primary_noun",
+            Self::Noun => "noun",
+            Self::NounList => "noun_list",
+            Self::NounsWithCase => "nouns_with_case",
+            Self::Program => "program",
+            Self::Sentence => "sentence",
+            Self::Verb => "verb",
+            Self::MeaClause => "mea_clause",
+            Self::Procedure => "procedure",
+            Self::Module => "module",
+            Self::Import => "import",
+            Self::VarDecl => "var_decl",
+            Self::PredicateDecl => "predicate_decl",
+            Self::EventCond => "event_cond",
+            Self::Cond => "cond",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ParseError {
-    EndOfFile,
-    UnexpectedToken { expected: String, actual: Token },
+    EndOfFile {
+        expected: Vec<Expected>,
+        rule: Rule,
+        span: Span,
+    },
+    UnexpectedToken {
+        expected: Vec<Expected>,
+        rule: Rule,
+        actual: Token,
+    },
 }
 
-pub struct State<'a> {
-    tokens: &'a [Token],
+impl ParseError {
+    #[must_use]
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::EndOfFile { span, .. } => *span,
+            Self::UnexpectedToken { actual, .. } => actual.span,
+        }
+    }
+
+    /// A stable identifier of the kind of error, independent of the message wording.
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::UnexpectedToken { .. } => "E0001",
+            Self::EndOfFile { .. } => "E0002",
+        }
+    }
+
+    #[must_use]
+    pub const fn rule(&self) -> Rule {
+        match self {
+            Self::EndOfFile { rule, .. } | Self::UnexpectedToken { rule, .. } => *rule,
+        }
+    }
+
+    #[must_use]
+    pub fn expected(&self) -> &[Expected] {
+        match self {
+            Self::EndOfFile { expected, .. } | Self::UnexpectedToken { expected, .. } => expected,
+        }
+    }
+
+    /// The expected set as one line, e.g. `'d / . / ad / adit`.
+    #[must_use]
+    pub fn expected_message(&self, locale: Locale) -> String {
+        self.expected()
+            .iter()
+            .map(|expected| locale.expected(*expected))
+            .collect::<Vec<_>>()
+            .join(" / ")
+    }
+}
+
+impl WithoutSpans for ParseError {
+    fn without_spans(&self) -> Self {
+        match self {
+            Self::EndOfFile { expected, rule, .. } => Self::EndOfFile {
+                expected: expected.clone(),
+                rule: *rule,
+                span: Span::default(),
+            },
+            Self::UnexpectedToken {
+                expected,
+                rule,
+                actual,
+            } => Self::UnexpectedToken {
+                expected: expected.clone(),
+                rule: *rule,
+                actual: actual.without_spans(),
+            },
+        }
+    }
+}
+
+// Runs over anything holding a token, so that a CST sentence can be parsed in place.
+pub struct State<'a, T = Token> {
+    tokens: &'a [T],
+    // span of the last consumed token
+    last: Span,
+    // empty span right after the last token
+    eof: Span,
 }
 
-impl<'a> State<'a> {
+impl<'a, T: AsRef<Token>> State<'a, T> {
     pub const fn is_empty(&self) -> bool {
         self.tokens.is_empty()
     }
 
-    pub const fn get_tokens(&self) -> &[Token] {
+    pub const fn get_tokens(&self) -> &'a [T] {
         self.tokens
     }
 
-    pub const fn new(tokens: &'a [Token]) -> Self {
-        Self { tokens }
+    pub fn new(tokens: &'a [T]) -> Self {
+        let eof = tokens.last().map_or_else(Span::default, |tok| {
+            let end = tok.as_ref().span.end;
+            Span::new(end, end)
+        });
+        Self {
+            tokens,
+            last: eof,
+            eof,
+        }
+    }
+
+    // Consumes the next token, which is known to exist.
+    fn bump(&mut self) {
+        self.last = self.tokens[0].as_ref().span;
+        self.tokens = &self.tokens[1..];
     }
 
-    pub fn next(&mut self) -> Result<Token, ParseError> {
+    pub fn next(&mut self, rule: Rule, expected: &[Expected]) -> Result<&'a Token, ParseError> {
         match self.tokens {
-            [] => Err(ParseError::EndOfFile),
-            [tok, ..] => {
-                self.tokens = &self.tokens[1..];
-                Ok(tok.clone())
+            [] => Err(ParseError::EndOfFile {
+                expected: expected.to_vec(),
+                rule,
+                span: self.eof,
+            }),
+            [tok, rest @ ..] => {
+                let tok = tok.as_ref();
+                self.tokens = rest;
+                self.last = tok.span;
+                Ok(tok)
             }
         }
     }
 
-    pub fn peek(&mut self) -> Option<Token> {
-        match self.tokens {
-            [] => None,
-            [tok, ..] => Some(tok.clone()),
-        }
+    pub fn peek_kind(&self) -> Option<&'a TokenKind> {
+        self.tokens.first().map(|tok| &tok.as_ref().kind)
+    }
+
+    // span of the next token, used as the start of the node about to be parsed
+    fn next_span(&self) -> Span {
+        self.tokens
+            .first()
+            .map_or(self.eof, |tok| tok.as_ref().span)
     }
 
-    // primary_noun = ident | "<" character* ">"
-    #[allow(clippy::match_wildcard_for_single_variants)]
+    // span from `start` up to the last consumed token
+    const fn span_from(&self, start: Span) -> Span {
+        start.to(self.last)
+    }
+
+    // primary_noun = ident | number | "<" character* ">"
     pub fn parse_primary_noun(&mut self) -> Result<PrimaryNoun, ParseError> {
-        let next = self.next()?;
-        match next {
-            Token::NormalIdent { ident } => Ok(PrimaryNoun::Ident { ident }),
-            Token::StringLiteral { literal } => Ok(PrimaryNoun::StringLiteral { literal }),
-            _ => Err(ParseError::UnexpectedToken {
-                expected: "（識別子か文字列リテラル）".to_string(),
+        let expected = [Expected::Ident, Expected::StringLiteral, Expected::Number];
+        let next = self.next(Rule::PrimaryNoun, &expected)?;
+        match &next.kind {
+            TokenKind::NormalIdent { ident } => Ok(PrimaryNoun::Ident {
+                ident: ident.clone(),
+                span: next.span,
+            }),
+            TokenKind::StringLiteral { literal, value } => Ok(PrimaryNoun::StringLiteral {
+                literal: literal.clone(),
+                value: value.clone(),
+                span: next.span,
+            }),
+            TokenKind::Number { number } => Ok(PrimaryNoun::Number {
+                number: *number,
+                span: next.span,
+            }),
+            TokenKind::Reserved(_) => Err(ParseError::UnexpectedToken {
+                expected: expected.to_vec(),
+                rule: Rule::PrimaryNoun,
                 actual: next.clone(),
             }),
         }
@@ -142,16 +616,19 @@ impl<'a> State<'a> {
 
     // noun = (primary_noun "'d")* primary_noun
     pub fn parse_noun(&mut self) -> Result<Noun, ParseError> {
+        let start = self.next_span();
         let mut pns = vec![];
         pns.push(self.parse_primary_noun()?);
-        while let [Token::Reserved(Reserved::ApostropheD), ..] = self.tokens {
-            self.tokens = &self.tokens[1..];
+        while self.peek_kind() == Some(&TokenKind::Reserved(Reserved::ApostropheD)) {
+            self.bump();
             pns.push(self.parse_primary_noun()?);
         }
         let head = pns.pop().unwrap();
         Ok(Noun {
             modifier: pns,
             head,
+            span: self.span_from(start),
+            id: NounId::fresh(),
         })
     }
 
@@ -161,29 +638,23 @@ impl<'a> State<'a> {
     pub fn parse_noun_list(&mut self) -> Result<Vec<Noun>, ParseError> {
         let mut nouns = vec![];
         nouns.push(self.parse_noun()?);
-        match self.peek() {
-            Some(Token::Reserved(Reserved::Ad)) => {
+        match self.peek_kind() {
+            Some(TokenKind::Reserved(Reserved::Ad)) => {
                 // noun "ad" noun
-                self.next()?;
+                self.bump();
                 nouns.push(self.parse_noun()?);
                 Ok(nouns)
             }
-            Some(Token::Reserved(Reserved::Adit)) => {
+            Some(TokenKind::Reserved(Reserved::Adit)) => {
                 // noun "adit" noun ("," noun)+
-                self.next()?;
+                self.bump();
                 nouns.push(self.parse_noun()?);
 
-                let expect_comma = self.next()?;
-                if expect_comma != Token::Reserved(Reserved::PunctuationComma) {
-                    return Err(ParseError::UnexpectedToken {
-                        expected: "（カンマ）".to_string(),
-                        actual: expect_comma,
-                    });
-                }
+                self.consume_or_die(Reserved::PunctuationComma, Rule::NounList)?;
                 nouns.push(self.parse_noun()?);
 
-                while let [Token::Reserved(Reserved::PunctuationComma), ..] = self.tokens {
-                    self.tokens = &self.tokens[1..];
+                while self.peek_kind() == Some(&TokenKind::Reserved(Reserved::PunctuationComma)) {
+                    self.bump();
                     nouns.push(self.parse_noun()?);
                 }
 
@@ -194,91 +665,114 @@ impl<'a> State<'a> {
     }
 
     // preposition = "el" | "lerj" | "fal" | "cecioj"
-    // nouns_with_case = preposition noun_list | noun_list "'c"
+    // nouns_with_case = preposition noun_list | noun_list ("'c" | "'i")
     pub fn parse_nouns_with_case(&mut self) -> Result<NounsWithCase, ParseError> {
-        if let Some(Token::Reserved(Reserved::Preposition(p))) = self.peek() {
-            self.next()?;
+        let start = self.next_span();
+        if let Some(&TokenKind::Reserved(Reserved::Preposition(p))) = self.peek_kind() {
+            self.bump();
             let nouns = self.parse_noun_list()?;
             Ok(NounsWithCase {
                 nouns,
                 case: Case::Preposition(p),
+                span: self.span_from(start),
             })
         } else {
             let nouns = self.parse_noun_list()?;
-            let next = self.next()?;
-            match next {
-                Token::Reserved(Reserved::ApostropheC) => Ok(NounsWithCase {
-                    nouns,
-                    case: Case::ApostropheC,
-                }),
-                _ => Err(ParseError::UnexpectedToken {
-                    expected: "'c".to_string(),
-                    actual: next,
-                }),
-            }
+            let expected = [
+                Expected::Reserved(Reserved::ApostropheC),
+                Expected::Reserved(Reserved::ApostropheI),
+            ];
+            let next = self.next(Rule::NounsWithCase, &expected)?;
+            let case = match next.kind {
+                TokenKind::Reserved(Reserved::ApostropheC) => Case::ApostropheC,
+                TokenKind::Reserved(Reserved::ApostropheI) => Case::ApostropheI,
+                _ => {
+                    return Err(ParseError::UnexpectedToken {
+                        expected: expected.to_vec(),
+                        rule: Rule::NounsWithCase,
+                        actual: next.clone(),
+                    })
+                }
+            };
+            Ok(NounsWithCase {
+                nouns,
+                case,
+                span: self.span_from(start),
+            })
         }
     }
 
     // var_decl = noun "es" noun
     pub fn parse_var_decl(&mut self) -> Result<Sentence, ParseError> {
         let noun1 = self.parse_noun()?;
-        self.consume_or_die(Reserved::Es, "es")?;
+        self.consume_or_die(Reserved::Es, Rule::VarDecl)?;
         let noun2 = self.parse_noun()?;
         Ok(Sentence::VarDecl(noun1, noun2))
     }
 
     // verb = ident
     pub fn parse_verb(&mut self) -> Result<Verb, ParseError> {
-        Ok(Verb(self.parse_ident()?))
+        let ident = self.parse_ident(Rule::Verb)?;
+        Ok(Verb(ident, self.last))
     }
 
-    pub fn consume_or_die(&mut self, reserved: Reserved, msg: &str) -> Result<(), ParseError> {
-        let next = self.next()?;
-        if next == Token::Reserved(reserved) {
+    pub fn consume_or_die(&mut self, reserved: Reserved, rule: Rule) -> Result<(), ParseError> {
+        let expected = [Expected::Reserved(reserved)];
+        let next = self.next(rule, &expected)?;
+        if next.kind == TokenKind::Reserved(reserved) {
             Ok(())
         } else {
             Err(ParseError::UnexpectedToken {
-                expected: msg.to_string(),
-                actual: next,
+                expected: expected.to_vec(),
+                rule,
+                actual: next.clone(),
             })
         }
     }
 
     // event_cond = noun "'st" verb "-il" "io"
     pub fn parse_event_cond(&mut self) -> Result<EventCond, ParseError> {
+        let start = self.next_span();
         let noun = self.parse_noun()?;
-        self.consume_or_die(Reserved::ApostropheSt, "'st")?;
+        self.consume_or_die(Reserved::ApostropheSt, Rule::EventCond)?;
         let verb = self.parse_verb()?;
-        self.consume_or_die(Reserved::HyphenIl, "-il")?;
-        self.consume_or_die(Reserved::Io, "io")?;
-        Ok(EventCond { noun, verb })
+        self.consume_or_die(Reserved::HyphenIl, Rule::EventCond)?;
+        self.consume_or_die(Reserved::Io, Rule::EventCond)?;
+        Ok(EventCond {
+            noun,
+            verb,
+            span: self.span_from(start),
+        })
     }
 
+    // noun verb nouns_with_case*
     pub fn parse_cond_elem(&mut self) -> Result<CondElem, ParseError> {
+        let start = self.next_span();
         let noun = self.parse_noun()?;
         let verb = self.parse_verb()?;
-        // When `felx`, `mal`, `.` comes, the parsing stops
-        let nouns_with_case = if let Some(Token::Reserved(
-            Reserved::Felx | Reserved::Mal | Reserved::PunctuationPeriod,
-        )) = self.peek()
-        {
-            None
-        } else {
-            let nouns_with_case = self.parse_nouns_with_case()?;
-            Some(nouns_with_case)
-        };
+        // When `felx`, `mal`, `.` or the end of input comes, the parsing stops
+        let mut nouns_with_case_array = vec![];
+        while !matches!(
+            self.peek_kind(),
+            None | Some(TokenKind::Reserved(
+                Reserved::Felx | Reserved::Mal | Reserved::PunctuationPeriod
+            ))
+        ) {
+            nouns_with_case_array.push(self.parse_nouns_with_case()?);
+        }
         Ok(CondElem {
             noun,
             verb,
-            nouns_with_case,
+            nouns_with_case_array,
+            span: self.span_from(start),
         })
     }
 
     // cond = noun verb nouns_with_case* ("mal" noun verb nouns_with_case*)*
     pub fn parse_cond(&mut self) -> Result<Cond, ParseError> {
         let mut cond_elems = vec![self.parse_cond_elem()?];
-        while let [Token::Reserved(Reserved::Mal), ..] = self.tokens {
-            self.tokens = &self.tokens[1..];
+        while self.peek_kind() == Some(&TokenKind::Reserved(Reserved::Mal)) {
+            self.bump();
             cond_elems.push(self.parse_cond_elem()?);
         }
         Ok(Cond(cond_elems))
@@ -286,30 +780,34 @@ impl<'a> State<'a> {
 
     // predicate_decl = noun_list "'st" verb "-o" ":" cond
     pub fn parse_predicate_decl(&mut self) -> Result<Sentence, ParseError> {
+        let start = self.next_span();
         let noun_list = self.parse_noun_list()?;
-        self.consume_or_die(Reserved::ApostropheSt, "'st")?;
+        self.consume_or_die(Reserved::ApostropheSt, Rule::PredicateDecl)?;
         let verb = self.parse_verb()?;
-        self.consume_or_die(Reserved::HyphenO, "-o")?;
-        self.consume_or_die(Reserved::PunctuationColon, ":")?;
+        self.consume_or_die(Reserved::HyphenO, Rule::PredicateDecl)?;
+        self.consume_or_die(Reserved::PunctuationColon, Rule::PredicateDecl)?;
         let cond = self.parse_cond()?;
         Ok(Sentence::PredicateDecl {
             noun_list,
             verb,
             cond,
+            span: self.span_from(start),
         })
     }
 
     // module = ident
     pub fn parse_module(&mut self) -> Result<Module, ParseError> {
-        Ok(Module(self.parse_ident()?))
+        let ident = self.parse_ident(Rule::Module)?;
+        Ok(Module(ident, self.last))
     }
 
-    pub fn parse_ident(&mut self) -> Result<String, ParseError> {
-        let next = self.next()?;
-        match next {
-            Token::NormalIdent { ident } => Ok(ident),
+    pub fn parse_ident(&mut self, rule: Rule) -> Result<Symbol, ParseError> {
+        let next = self.next(rule, &[Expected::Ident])?;
+        match &next.kind {
+            TokenKind::NormalIdent { ident } => Ok(ident.clone()),
             _ => Err(ParseError::UnexpectedToken {
-                expected: "（識別子）".to_string(),
+                expected: vec![Expected::Ident],
+                rule,
                 actual: next.clone(),
             }),
         }
@@ -319,102 +817,96 @@ impl<'a> State<'a> {
     //        ident | ident "ad" ident | ident "adit" ident ("," ident)+
     //      )
     pub fn parse_import(&mut self) -> Result<Import, ParseError> {
-        self.consume_or_die(Reserved::Lus, "lus")?;
+        let start = self.next_span();
+        self.consume_or_die(Reserved::Lus, Rule::Import)?;
         let mut module_path = vec![self.parse_module()?];
-        self.consume_or_die(Reserved::ApostropheD, "'d")?;
+        self.consume_or_die(Reserved::ApostropheD, Rule::Import)?;
 
-        // needs to read two tokens ahead.
+        // The token after an ident decides what the ident was.
         // If `'d`, it's `module 'd`
         // If period, it's `ident .`
         // If `ad`, it's `ident ad`
         // If `adit`, it's `ident adit`
         loop {
-            match self.tokens {
-                [] | [Token::NormalIdent { .. }] => return Err(ParseError::EndOfFile),
-                [Token::NormalIdent { ident }, Token::Reserved(Reserved::ApostropheD), ..] => {
-                    module_path.push(Module(ident.to_string()));
-                    self.tokens = &self.tokens[2..];
+            let ident = self.parse_ident(Rule::Import)?;
+            let ident_span = self.last;
+            let idents = match self.peek_kind() {
+                Some(TokenKind::Reserved(Reserved::ApostropheD)) => {
+                    module_path.push(Module(ident, ident_span));
+                    self.bump();
                     continue;
                 }
-                [Token::NormalIdent { ident }, Token::Reserved(Reserved::PunctuationPeriod), ..] => {
-                    let import = Import {
-                        module_path,
-                        idents: vec![ident.to_string()],
-                    };
-                    self.tokens = &self.tokens[1..];
-                    return Ok(import);
-                }
-                [Token::NormalIdent { ident }, Token::Reserved(Reserved::Ad), Token::NormalIdent { ident: ident2 }] =>
-                {
-                    let import = Import {
-                        module_path,
-                        idents: vec![ident.to_string(), ident2.to_string()],
-                    };
-                    self.tokens = &self.tokens[3..];
-                    return Ok(import);
+                Some(TokenKind::Reserved(Reserved::PunctuationPeriod)) => vec![ident],
+                Some(TokenKind::Reserved(Reserved::Ad)) => {
+                    self.bump();
+                    vec![ident, self.parse_ident(Rule::Import)?]
                 }
-                [Token::NormalIdent { ident }, Token::Reserved(Reserved::Adit), Token::NormalIdent { ident: ident2 }, Token::Reserved(Reserved::PunctuationComma), Token::NormalIdent { ident: ident3 }, ..] =>
-                {
-                    let mut ident_list =
-                        vec![ident.to_string(), ident2.to_string(), ident3.to_string()];
-                    self.tokens = &self.tokens[5..];
-
-                    while let [Token::Reserved(Reserved::PunctuationComma), Token::NormalIdent { ident }, ..] =
-                        self.tokens
+                Some(TokenKind::Reserved(Reserved::Adit)) => {
+                    self.bump();
+                    let mut ident_list = vec![ident, self.parse_ident(Rule::Import)?];
+                    self.consume_or_die(Reserved::PunctuationComma, Rule::Import)?;
+                    ident_list.push(self.parse_ident(Rule::Import)?);
+                    while self.peek_kind() == Some(&TokenKind::Reserved(Reserved::PunctuationComma))
                     {
-                        self.tokens = &self.tokens[2..];
-                        ident_list.push(ident.to_string());
+                        self.bump();
+                        ident_list.push(self.parse_ident(Rule::Import)?);
                     }
-
-                    return Ok(Import {
-                        module_path,
-                        idents: ident_list,
-                    });
+                    ident_list
                 }
-                [Token::NormalIdent { .. }, tok, ..] => {
+                _ => {
+                    let expected = [
+                        Expected::Reserved(Reserved::ApostropheD),
+                        Expected::Reserved(Reserved::PunctuationPeriod),
+                        Expected::Reserved(Reserved::Ad),
+                        Expected::Reserved(Reserved::Adit),
+                    ];
+                    let actual = self.next(Rule::Import, &expected)?;
                     return Err(ParseError::UnexpectedToken {
-                        expected: "'d / . / ad / adit".to_string(),
-                        actual: tok.clone(),
-                    })
-                }
-
-                [tok, ..] => {
-                    return Err(ParseError::UnexpectedToken {
-                        expected: "（識別子）".to_string(),
-                        actual: tok.clone(),
-                    })
+                        expected: expected.to_vec(),
+                        rule: Rule::Import,
+                        actual: actual.clone(),
+                    });
                 }
-            }
+            };
+            return Ok(Import {
+                module_path,
+                idents,
+                span: self.span_from(start),
+            });
         }
     }
 
     // (ident "'d")* ident "es" noun_list
     pub fn parse_mea_elem(&mut self) -> Result<MeaElem, ParseError> {
-        let mut idents = vec![self.parse_ident()?];
-        while let [Token::Reserved(Reserved::ApostropheD), ..] = self.tokens {
-            self.tokens = &self.tokens[1..];
-            idents.push(self.parse_ident()?);
+        let start = self.next_span();
+        let mut idents = vec![self.parse_ident(Rule::MeaClause)?];
+        while self.peek_kind() == Some(&TokenKind::Reserved(Reserved::ApostropheD)) {
+            self.bump();
+            idents.push(self.parse_ident(Rule::MeaClause)?);
         }
-        self.consume_or_die(Reserved::Es, "es")?;
+        let named_parameter_span = self.span_from(start);
+        self.consume_or_die(Reserved::Es, Rule::MeaClause)?;
         let noun_list = self.parse_noun_list()?;
         let head = idents.pop().unwrap();
         let named_parameter = NamedParameter {
             modifiers: idents,
             head,
+            span: named_parameter_span,
         };
         Ok(MeaElem {
             named_parameter,
             noun_list,
+            span: self.span_from(start),
         })
     }
 
     // mea_clause = "mea" (ident "'d")* ident "es" noun_list
     //              ("mal" (ident "'d")* ident "es" noun_list)*
     pub fn parse_mea_clause(&mut self) -> Result<Vec<MeaElem>, ParseError> {
-        self.consume_or_die(Reserved::Mea, "mea")?;
+        self.consume_or_die(Reserved::Mea, Rule::MeaClause)?;
         let mut mea_clause = vec![self.parse_mea_elem()?];
-        while let [Token::Reserved(Reserved::Mal), ..] = self.tokens {
-            self.tokens = &self.tokens[1..];
+        while self.peek_kind() == Some(&TokenKind::Reserved(Reserved::Mal)) {
+            self.bump();
             mea_clause.push(self.parse_mea_elem()?);
         }
         Ok(mea_clause)
@@ -422,19 +914,22 @@ impl<'a> State<'a> {
 
     // procedure = verb noun nouns_with_case* mea_clause?
     pub fn parse_procedure(&mut self) -> Result<Procedure, ParseError> {
+        let start = self.next_span();
         let verb = self.parse_verb()?;
         let noun = self.parse_noun()?;
 
         let mut nouns_with_case_array = vec![];
-        loop {
-            if let Some(Token::Reserved(Reserved::Mea | Reserved::PunctuationPeriod)) = self.peek()
-            {
-                break;
-            }
+        // When `mea`, `melx`, `.` or the end of input comes, the nouns_with_case* part ends
+        while !matches!(
+            self.peek_kind(),
+            None | Some(TokenKind::Reserved(
+                Reserved::Mea | Reserved::Melx | Reserved::PunctuationPeriod
+            ))
+        ) {
             nouns_with_case_array.push(self.parse_nouns_with_case()?);
         }
 
-        let mea_clause = if self.peek() == Some(Token::Reserved(Reserved::Mea)) {
+        let mea_clause = if self.peek_kind() == Some(&TokenKind::Reserved(Reserved::Mea)) {
             let mea_clause = self.parse_mea_clause()?;
             Some(mea_clause)
         } else {
@@ -446,6 +941,167 @@ impl<'a> State<'a> {
             noun,
             nouns_with_case_array,
             mea_clause,
+            span: self.span_from(start),
         })
     }
+
+    // event_cond* "elx" "shrlo" procedure ("melx" "shrlo" procedure)*
+    // event_cond* cond "felx" "shrlo" procedure ("melx" "shrlo" procedure)*
+    pub fn parse_event_handler(&mut self) -> Result<Sentence, ParseError> {
+        let start = self.next_span();
+        let mut event_conds = vec![];
+        while self.lookahead_event_cond() {
+            event_conds.push(self.parse_event_cond()?);
+        }
+
+        if self.peek_kind() == Some(&TokenKind::Reserved(Reserved::Elx)) {
+            self.bump();
+            self.consume_or_die(Reserved::Shrlo, Rule::Sentence)?;
+            let procedures = self.parse_procedure_chain()?;
+            Ok(Sentence::EventHandler {
+                event_conds,
+                procedures,
+                span: self.span_from(start),
+            })
+        } else {
+            let guard = self.parse_cond()?;
+            self.consume_or_die(Reserved::Felx, Rule::Sentence)?;
+            self.consume_or_die(Reserved::Shrlo, Rule::Sentence)?;
+            let procedures = self.parse_procedure_chain()?;
+            Ok(Sentence::GuardedEventHandler {
+                event_conds,
+                guard,
+                procedures,
+                span: self.span_from(start),
+            })
+        }
+    }
+
+    // procedure ("melx" "shrlo" procedure)*
+    pub fn parse_procedure_chain(&mut self) -> Result<Vec<Procedure>, ParseError> {
+        let mut procedures = vec![self.parse_procedure()?];
+        while self.peek_kind() == Some(&TokenKind::Reserved(Reserved::Melx)) {
+            self.bump();
+            self.consume_or_die(Reserved::Shrlo, Rule::Sentence)?;
+            procedures.push(self.parse_procedure()?);
+        }
+        Ok(procedures)
+    }
+
+    // sentence "."
+    pub fn parse_terminated_sentence(&mut self) -> Result<Sentence, ParseError> {
+        let sentence = self.parse_sentence()?;
+        self.consume_or_die(Reserved::PunctuationPeriod, Rule::Program)?;
+        Ok(sentence)
+    }
+
+    // sentence = procedure
+    //   | import
+    //   | var_decl
+    //   | predicate_decl
+    //   | event_cond* "elx" "shrlo" procedure ("melx" "shrlo" procedure)*
+    //   | event_cond* cond "felx" "shrlo" procedure ("melx" "shrlo" procedure)*
+    pub fn parse_sentence(&mut self) -> Result<Sentence, ParseError> {
+        if self.peek_kind() == Some(&TokenKind::Reserved(Reserved::Lus)) {
+            return Ok(self.parse_import()?.into());
+        }
+
+        // `elx` / `felx` can only appear in an event handler, and
+        // they never appear inside a nested construct
+        let sentence_end = self
+            .tokens
+            .iter()
+            .position(|tok| tok.as_ref().kind == TokenKind::Reserved(Reserved::PunctuationPeriod))
+            .unwrap_or(self.tokens.len());
+        if self.tokens[..sentence_end].iter().any(|tok| {
+            matches!(
+                tok.as_ref().kind,
+                TokenKind::Reserved(Reserved::Elx | Reserved::Felx)
+            )
+        }) {
+            return self.parse_event_handler();
+        }
+
+        // noun "es" ...            => var_decl
+        // noun_list "'st" verb "-o" => predicate_decl
+        // otherwise                 => procedure
+        if let Some(i) = skip_noun(self.tokens, 0) {
+            if matches!(
+                kind_at(self.tokens, i),
+                Some(TokenKind::Reserved(Reserved::Es))
+            ) {
+                return self.parse_var_decl();
+            }
+        }
+        if let Some(i) = skip_noun_list(self.tokens, 0) {
+            if matches!(
+                kind_at(self.tokens, i),
+                Some(TokenKind::Reserved(Reserved::ApostropheSt))
+            ) {
+                return self.parse_predicate_decl();
+            }
+        }
+        Ok(self.parse_procedure()?.into())
+    }
+
+    // noun "'st" verb "-il"
+    fn lookahead_event_cond(&self) -> bool {
+        skip_noun(self.tokens, 0).is_some_and(|i| {
+            matches!(
+                (
+                    kind_at(self.tokens, i),
+                    kind_at(self.tokens, i + 1),
+                    kind_at(self.tokens, i + 2)
+                ),
+                (
+                    Some(TokenKind::Reserved(Reserved::ApostropheSt)),
+                    Some(TokenKind::NormalIdent { .. }),
+                    Some(TokenKind::Reserved(Reserved::HyphenIl))
+                )
+            )
+        })
+    }
+}
+
+fn kind_at<T: AsRef<Token>>(tokens: &[T], i: usize) -> Option<&TokenKind> {
+    tokens.get(i).map(|tok| &tok.as_ref().kind)
+}
+
+// Returns the index right after the noun starting at `i`, without consuming anything.
+// noun = (primary_noun "'d")* primary_noun
+fn skip_noun<T: AsRef<Token>>(tokens: &[T], mut i: usize) -> Option<usize> {
+    loop {
+        match kind_at(tokens, i) {
+            Some(
+                TokenKind::NormalIdent { .. }
+                | TokenKind::StringLiteral { .. }
+                | TokenKind::Number { .. },
+            ) => i += 1,
+            _ => return None,
+        }
+        match kind_at(tokens, i) {
+            Some(TokenKind::Reserved(Reserved::ApostropheD)) => i += 1,
+            _ => return Some(i),
+        }
+    }
+}
+
+// Returns the index right after the noun_list starting at `i`, without consuming anything.
+// noun_list = noun | noun "ad" noun | noun "adit" noun ("," noun)+
+fn skip_noun_list<T: AsRef<Token>>(tokens: &[T], i: usize) -> Option<usize> {
+    let mut i = skip_noun(tokens, i)?;
+    match kind_at(tokens, i) {
+        Some(TokenKind::Reserved(Reserved::Ad)) => skip_noun(tokens, i + 1),
+        Some(TokenKind::Reserved(Reserved::Adit)) => {
+            i = skip_noun(tokens, i + 1)?;
+            while matches!(
+                kind_at(tokens, i),
+                Some(TokenKind::Reserved(Reserved::PunctuationComma))
+            ) {
+                i = skip_noun(tokens, i + 1)?;
+            }
+            Some(i)
+        }
+        _ => Some(i),
+    }
 }
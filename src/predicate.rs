@@ -0,0 +1,342 @@
+//! Checks of predicate declarations, `noun_list'st verb-o : cond`.
+//!
+//! The condition of a predicate may only use its parameters (and the builtins), and every
+//! parameter must be used. A predicate is used as the verb of a condition, whose subject and
+//! nouns with case are its arguments, one per parameter. Predicates may not be recursive.
+//! Undefined names are left to the resolver.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    parser::{Cond, Noun, NounId, Sentence},
+    resolve::{DefinitionKind, Resolution},
+    span::Span,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PredicateErrorKind {
+    UnusedParameter(String),
+    UnboundName(String),
+    Arity {
+        predicate: String,
+        expected: usize,
+        found: usize,
+    },
+    // the predicates in the order they use each other, ending with the first one again
+    Recursive(Vec<String>),
+    Duplicate {
+        predicate: String,
+        previous: Span,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PredicateError {
+    pub kind: PredicateErrorKind,
+    pub span: Span,
+}
+
+impl PredicateError {
+    /// A stable identifier of the kind of error, independent of the message wording.
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self.kind {
+            PredicateErrorKind::UnusedParameter(_) => "E0601",
+            PredicateErrorKind::UnboundName(_) => "E0602",
+            PredicateErrorKind::Arity { .. } => "E0603",
+            PredicateErrorKind::Recursive(_) => "E0604",
+            PredicateErrorKind::Duplicate { .. } => "E0605",
+        }
+    }
+}
+
+struct Declaration<'a> {
+    name: &'a str,
+    parameters: &'a [Noun],
+    cond: &'a Cond,
+    // of the verb
+    span: Span,
+}
+
+/// Checks the predicate declarations of `sentences` and the conditions using them.
+#[must_use]
+pub fn check_predicates(sentences: &[Sentence], resolution: &Resolution) -> Vec<PredicateError> {
+    let mut errors = vec![];
+    let mut declarations: Vec<Declaration> = vec![];
+    for sentence in sentences {
+        let Sentence::PredicateDecl {
+            noun_list,
+            verb,
+            cond,
+            ..
+        } = sentence
+        else {
+            continue;
+        };
+        if let Some(previous) = declarations.iter().find(|d| d.name == verb.0.as_str()) {
+            errors.push(PredicateError {
+                kind: PredicateErrorKind::Duplicate {
+                    predicate: verb.0.to_string(),
+                    previous: previous.span,
+                },
+                span: verb.1,
+            });
+            continue;
+        }
+        declarations.push(Declaration {
+            name: &verb.0,
+            parameters: noun_list,
+            cond,
+            span: verb.1,
+        });
+    }
+    let index = declarations
+        .iter()
+        .enumerate()
+        .map(|(i, declaration)| (declaration.name, i))
+        .collect::<HashMap<_, _>>();
+
+    for declaration in &declarations {
+        check_body(declaration, resolution, &mut errors);
+    }
+
+    let guards = sentences.iter().filter_map(|sentence| match sentence {
+        Sentence::GuardedEventHandler { guard, .. } => Some(guard),
+        _ => None,
+    });
+    for cond in declarations.iter().map(|d| d.cond).chain(guards) {
+        for elem in &cond.0 {
+            let Some(&i) = index.get(elem.verb.0.as_str()) else {
+                continue;
+            };
+            let expected = declarations[i].parameters.len();
+            let found = 1 + elem
+                .nouns_with_case_array
+                .iter()
+                .map(|nouns_with_case| nouns_with_case.nouns.len())
+                .sum::<usize>();
+            if expected != found {
+                errors.push(PredicateError {
+                    kind: PredicateErrorKind::Arity {
+                        predicate: elem.verb.0.to_string(),
+                        expected,
+                        found,
+                    },
+                    span: elem.span,
+                });
+            }
+        }
+    }
+
+    let calls = declarations
+        .iter()
+        .map(|declaration| {
+            declaration
+                .cond
+                .0
+                .iter()
+                .filter_map(|elem| index.get(elem.verb.0.as_str()).copied())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut visited = vec![false; declarations.len()];
+    for i in 0..declarations.len() {
+        find_recursion(i, &calls, &mut visited, &mut vec![], &mut |cycle| {
+            errors.push(PredicateError {
+                kind: PredicateErrorKind::Recursive(
+                    cycle
+                        .iter()
+                        .map(|&j| declarations[j].name.to_string())
+                        .collect(),
+                ),
+                span: declarations[cycle[0]].span,
+            });
+        });
+    }
+    errors
+}
+
+// Unused parameters and names other than parameters in the condition
+fn check_body(
+    declaration: &Declaration,
+    resolution: &Resolution,
+    errors: &mut Vec<PredicateError>,
+) {
+    let mut used: HashSet<NounId> = HashSet::new();
+    let nouns = declaration.cond.0.iter().flat_map(|elem| {
+        std::iter::once(&elem.noun).chain(
+            elem.nouns_with_case_array
+                .iter()
+                .flat_map(|nouns_with_case| &nouns_with_case.nouns),
+        )
+    });
+    for noun in nouns {
+        let Some(definition) = resolution.definition_of(noun) else {
+            continue;
+        };
+        match definition.kind {
+            DefinitionKind::Parameter => {
+                used.extend(definition.noun);
+            }
+            DefinitionKind::Builtin => {}
+            _ => errors.push(PredicateError {
+                kind: PredicateErrorKind::UnboundName(definition.name.clone()),
+                span: noun.modifier.first().unwrap_or(&noun.head).span(),
+            }),
+        }
+    }
+    for parameter in declaration.parameters {
+        let Some(definition) = resolution.definition_of(parameter) else {
+            continue;
+        };
+        if definition.kind == DefinitionKind::Parameter && !used.contains(&parameter.id) {
+            errors.push(PredicateError {
+                kind: PredicateErrorKind::UnusedParameter(definition.name.clone()),
+                span: parameter.span,
+            });
+        }
+    }
+}
+
+// Depth-first search from `i`, calling `report` with every cycle closed by a call back into
+// `stack`
+fn find_recursion(
+    i: usize,
+    calls: &[Vec<usize>],
+    visited: &mut [bool],
+    stack: &mut Vec<usize>,
+    report: &mut impl FnMut(&[usize]),
+) {
+    if let Some(start) = stack.iter().position(|&j| j == i) {
+        let mut cycle = stack[start..].to_vec();
+        cycle.push(i);
+        report(&cycle);
+        return;
+    }
+    if visited[i] {
+        return;
+    }
+    visited[i] = true;
+    stack.push(i);
+    for &j in &calls[i] {
+        find_recursion(j, calls, visited, stack, report);
+    }
+    stack.pop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::Range;
+
+    use crate::{parse_program, resolve::resolve, span::Position};
+
+    fn check(source: &str) -> Vec<(PredicateErrorKind, Range<usize>)> {
+        let sentences = parse_program(source).unwrap();
+        let (resolution, _) = resolve(&sentences);
+        check_predicates(&sentences, &resolution)
+            .into_iter()
+            .map(|err| (err.kind, err.span.range()))
+            .collect()
+    }
+
+    #[test]
+    fn selsurle() {
+        // `es_tydivexy` is declared with two parameters but given one argument
+        let arity = |range| {
+            (
+                PredicateErrorKind::Arity {
+                    predicate: "es_tydivexy".to_string(),
+                    expected: 2,
+                    found: 1,
+                },
+                range,
+            )
+        };
+        let source = include_str!("../selsurle.jmk4");
+        let first = source
+            .find("kernumesaxm'd pestavilersnelyo es_tydivexy")
+            .unwrap();
+        let second = source.find("kernumesaxm'd snelyo es_tydivexy").unwrap();
+        assert_eq!(
+            check(source),
+            vec![arity(first..first + 42), arity(second..second + 32)]
+        );
+    }
+
+    #[test]
+    fn parameters() {
+        assert_eq!(
+            check("lus jmk4'd jerldir. a ad b'st p-o : a mol cecioj 1 ad iu mal jerldir'd xakant mol."),
+            vec![
+                (PredicateErrorKind::UnboundName("jerldir".to_string()), 61..68),
+                (PredicateErrorKind::UnusedParameter("b".to_string()), 25..26),
+            ]
+        );
+    }
+
+    #[test]
+    fn arity() {
+        let arity = |predicate: &str, expected, found, range| {
+            (
+                PredicateErrorKind::Arity {
+                    predicate: predicate.to_string(),
+                    expected,
+                    found,
+                },
+                range,
+            )
+        };
+        assert_eq!(
+            check("a'st p-o : a mol. b'st q-o : b p cecioj 1. iu q cecioj 1 ad 2 felx shrlo is iu iu'c."),
+            vec![arity("p", 1, 2, 29..41), arity("q", 1, 3, 43..61)]
+        );
+    }
+
+    #[test]
+    fn recursion() {
+        assert_eq!(
+            check("a'st p-o : a q. a'st q-o : a p mal a mol. a'st r-o : a r. a'st s-o : a p."),
+            vec![
+                (
+                    PredicateErrorKind::Recursive(vec![
+                        "p".to_string(),
+                        "q".to_string(),
+                        "p".to_string()
+                    ]),
+                    5..6
+                ),
+                (
+                    PredicateErrorKind::Recursive(vec!["r".to_string(), "r".to_string()]),
+                    47..48
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate() {
+        let errors = check("a'st p-o : a mol.\na'st p-o : a mol.");
+        assert_eq!(
+            errors,
+            vec![(
+                PredicateErrorKind::Duplicate {
+                    predicate: "p".to_string(),
+                    previous: Span::new(
+                        Position {
+                            offset: 5,
+                            line: 1,
+                            column: 6
+                        },
+                        Position {
+                            offset: 6,
+                            line: 1,
+                            column: 7
+                        }
+                    )
+                },
+                23..24
+            )]
+        );
+    }
+}
@@ -0,0 +1,415 @@
+//! Name resolution: links the nouns of a program to the imports and `es` declarations
+//! that introduce their names.
+//!
+//! Top-level names are visible in the whole program, before and after they are declared,
+//! since event handlers run long after the program has been read. The parameters of a
+//! predicate declaration are visible in its condition only, where they shadow the others.
+//! Only the first word of a noun is looked up: in `kernumesaxm'd snelyo`, `snelyo` is a part
+//! of whatever `kernumesaxm` is.
+
+use std::collections::HashMap;
+
+use crate::{
+    parser::{Cond, Import, Noun, NounId, NounsWithCase, PrimaryNoun, Procedure, Sentence},
+    span::Span,
+};
+
+/// The names the runtime provides without any import.
+pub const BUILTINS: [&str; 2] = ["iu", "iulo"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DefinitionKind {
+    // `lus jmk4'd jerldir` gives `jerldir` the module path `["jmk4"]`
+    Import { module_path: Vec<String> },
+    Variable,
+    Parameter,
+    Builtin,
+}
+
+/// Something a name can refer to. The span is that of the import sentence or of the declared
+/// noun, and empty for builtins.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Definition {
+    pub name: String,
+    pub kind: DefinitionKind,
+    pub span: Span,
+    // the declared noun of a variable or parameter
+    pub noun: Option<NounId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ResolveErrorKind {
+    Undefined(String),
+    // `previous` is the span of the first definition
+    Duplicate { name: String, previous: Span },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResolveError {
+    pub kind: ResolveErrorKind,
+    pub span: Span,
+}
+
+impl ResolveError {
+    /// A stable identifier of the kind of error, independent of the message wording.
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self.kind {
+            ResolveErrorKind::Undefined(_) => "E0201",
+            ResolveErrorKind::Duplicate { .. } => "E0202",
+        }
+    }
+}
+
+/// The symbol table of a program, and what each of its nouns refers to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Resolution {
+    pub definitions: Vec<Definition>,
+    // index into `definitions` for each resolved noun
+    nouns: HashMap<NounId, usize>,
+}
+
+impl Resolution {
+    /// What `noun` refers to. `None` for literals, numbers and undefined names.
+    #[must_use]
+    pub fn definition_of(&self, noun: &Noun) -> Option<&Definition> {
+        self.nouns.get(&noun.id).map(|&i| &self.definitions[i])
+    }
+}
+
+/// Resolves the names of `sentences` against their declarations and `BUILTINS`.
+#[must_use]
+pub fn resolve(sentences: &[Sentence]) -> (Resolution, Vec<ResolveError>) {
+    resolve_with(sentences, &BUILTINS)
+}
+
+/// Same as `resolve`, with the names the runtime provides given explicitly.
+#[must_use]
+pub fn resolve_with(sentences: &[Sentence], builtins: &[&str]) -> (Resolution, Vec<ResolveError>) {
+    let mut resolver = Resolver::default();
+    for &name in builtins {
+        resolver.define(name, DefinitionKind::Builtin, Span::default(), None);
+    }
+    for sentence in sentences {
+        match sentence {
+            Sentence::Import(import) => resolver.declare_import(import),
+            Sentence::VarDecl(noun, _) => {
+                if let Some(name) = declared_name(noun) {
+                    let i =
+                        resolver.define(name, DefinitionKind::Variable, noun.span, Some(noun.id));
+                    resolver.resolution.nouns.insert(noun.id, i);
+                }
+            }
+            _ => {}
+        }
+    }
+    for sentence in sentences {
+        resolver.sentence(sentence);
+    }
+    (resolver.resolution, resolver.errors)
+}
+
+// The name a declaration introduces, if it is a single identifier
+fn declared_name(noun: &Noun) -> Option<&str> {
+    match noun {
+        Noun {
+            modifier,
+            head: PrimaryNoun::Ident { ident, .. },
+            ..
+        } if modifier.is_empty() => Some(ident),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct Resolver {
+    resolution: Resolution,
+    globals: HashMap<String, usize>,
+    // the parameters of the predicate declaration being resolved
+    locals: HashMap<String, usize>,
+    errors: Vec<ResolveError>,
+}
+
+impl Resolver {
+    fn define(
+        &mut self,
+        name: &str,
+        kind: DefinitionKind,
+        span: Span,
+        noun: Option<NounId>,
+    ) -> usize {
+        let scope = if kind == DefinitionKind::Parameter {
+            &mut self.locals
+        } else {
+            &mut self.globals
+        };
+        if let Some(&previous) = scope.get(name) {
+            let previous = &self.resolution.definitions[previous];
+            // a builtin may be redefined; it then means the program's own definition
+            if previous.kind != DefinitionKind::Builtin {
+                self.errors.push(ResolveError {
+                    kind: ResolveErrorKind::Duplicate {
+                        name: name.to_string(),
+                        previous: previous.span,
+                    },
+                    span,
+                });
+            }
+        }
+        let i = self.resolution.definitions.len();
+        self.resolution.definitions.push(Definition {
+            name: name.to_string(),
+            kind,
+            span,
+            noun,
+        });
+        scope.insert(name.to_string(), i);
+        i
+    }
+
+    fn declare_import(&mut self, import: &Import) {
+        let module_path = import
+            .module_path
+            .iter()
+            .map(|module| module.0.to_string())
+            .collect::<Vec<_>>();
+        for ident in &import.idents {
+            let kind = DefinitionKind::Import {
+                module_path: module_path.clone(),
+            };
+            self.define(ident, kind, import.span, None);
+        }
+    }
+
+    fn sentence(&mut self, sentence: &Sentence) {
+        match sentence {
+            Sentence::Procedure(procedure) => self.procedure(procedure),
+            Sentence::Import(_) => {}
+            Sentence::VarDecl(noun1, noun2) => {
+                if declared_name(noun1).is_none() {
+                    self.noun(noun1);
+                }
+                self.noun(noun2);
+            }
+            Sentence::PredicateDecl {
+                noun_list, cond, ..
+            } => {
+                for noun in noun_list {
+                    if let Some(name) = declared_name(noun) {
+                        let i =
+                            self.define(name, DefinitionKind::Parameter, noun.span, Some(noun.id));
+                        self.resolution.nouns.insert(noun.id, i);
+                    } else {
+                        self.noun(noun);
+                    }
+                }
+                self.cond(cond);
+                self.locals.clear();
+            }
+            Sentence::EventHandler {
+                event_conds,
+                procedures,
+                ..
+            } => {
+                for event_cond in event_conds {
+                    self.noun(&event_cond.noun);
+                }
+                for procedure in procedures {
+                    self.procedure(procedure);
+                }
+            }
+            Sentence::GuardedEventHandler {
+                event_conds,
+                guard,
+                procedures,
+                ..
+            } => {
+                for event_cond in event_conds {
+                    self.noun(&event_cond.noun);
+                }
+                self.cond(guard);
+                for procedure in procedures {
+                    self.procedure(procedure);
+                }
+            }
+        }
+    }
+
+    fn procedure(&mut self, procedure: &Procedure) {
+        self.noun(&procedure.noun);
+        self.nouns_with_case(&procedure.nouns_with_case_array);
+        // the named parameters belong to the verb, only their values are looked up
+        for mea_elem in procedure.mea_clause.iter().flatten() {
+            for noun in &mea_elem.noun_list {
+                self.noun(noun);
+            }
+        }
+    }
+
+    fn cond(&mut self, cond: &Cond) {
+        for elem in &cond.0 {
+            self.noun(&elem.noun);
+            self.nouns_with_case(&elem.nouns_with_case_array);
+        }
+    }
+
+    fn nouns_with_case(&mut self, nouns_with_case_array: &[NounsWithCase]) {
+        for nouns_with_case in nouns_with_case_array {
+            for noun in &nouns_with_case.nouns {
+                self.noun(noun);
+            }
+        }
+    }
+
+    fn noun(&mut self, noun: &Noun) {
+        let root = noun.modifier.first().unwrap_or(&noun.head);
+        let PrimaryNoun::Ident { ident, span } = root else {
+            return;
+        };
+        match self
+            .locals
+            .get(ident.as_str())
+            .or_else(|| self.globals.get(ident.as_str()))
+        {
+            Some(&i) => {
+                self.resolution.nouns.insert(noun.id, i);
+            }
+            None => self.errors.push(ResolveError {
+                kind: ResolveErrorKind::Undefined(ident.to_string()),
+                span: *span,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, span::Position};
+
+    fn resolve_source(source: &str) -> (Vec<Sentence>, Resolution, Vec<ResolveError>) {
+        let sentences = parse_program(source).unwrap();
+        let (resolution, errors) = resolve(&sentences);
+        (sentences, resolution, errors)
+    }
+
+    #[test]
+    fn same_range_in_another_program() {
+        let (sentences, resolution, _) = resolve_source("selsurle es iu.");
+        let other = parse_program("selsurle es 10.").unwrap();
+        let (Sentence::VarDecl(_, iu), Sentence::VarDecl(_, number)) = (&sentences[0], &other[0])
+        else {
+            panic!("{sentences:?}");
+        };
+        assert_eq!(iu.span, number.span);
+        assert_eq!(resolution.definition_of(iu).unwrap().name, "iu");
+        assert_eq!(resolution.definition_of(number), None);
+    }
+
+    #[test]
+    fn selsurle() {
+        let (sentences, resolution, errors) = resolve_source(include_str!("../selsurle.jmk4"));
+        assert_eq!(errors, vec![]);
+        // is jerldir'd xakant <selsurle>'c.
+        let Sentence::Procedure(procedure) = &sentences[3] else {
+            panic!("{:?}", sentences[3]);
+        };
+        let definition = resolution.definition_of(&procedure.noun).unwrap();
+        assert_eq!(definition.name, "jerldir");
+        assert_eq!(
+            definition.kind,
+            DefinitionKind::Import {
+                module_path: vec!["jmk4".to_string()]
+            }
+        );
+        assert_eq!(definition.span.range(), 0..41);
+        let literal = &procedure.nouns_with_case_array[0].nouns[0];
+        assert_eq!(resolution.definition_of(literal), None);
+    }
+
+    #[test]
+    fn variables_and_builtins() {
+        let (sentences, resolution, errors) = resolve_source("is selsurle iulo'c. selsurle es iu.");
+        assert_eq!(errors, vec![]);
+        let Sentence::Procedure(procedure) = &sentences[0] else {
+            panic!("{:?}", sentences[0]);
+        };
+        let selsurle = resolution.definition_of(&procedure.noun).unwrap();
+        assert_eq!(
+            (selsurle.kind.clone(), selsurle.span.range()),
+            (DefinitionKind::Variable, 20..28)
+        );
+        let iulo = &procedure.nouns_with_case_array[0].nouns[0];
+        assert_eq!(
+            resolution.definition_of(iulo).unwrap().kind,
+            DefinitionKind::Builtin
+        );
+    }
+
+    #[test]
+    fn predicate_parameters() {
+        let (sentences, resolution, errors) = resolve_source(
+            "nert ad ektir'st es_tydivexy-o : ektir mol cecioj 4 ad 204. is nert iu'c.",
+        );
+        let Sentence::PredicateDecl { cond, .. } = &sentences[0] else {
+            panic!("{:?}", sentences[0]);
+        };
+        let ektir = resolution.definition_of(&cond.0[0].noun).unwrap();
+        assert_eq!(
+            (ektir.kind.clone(), ektir.span.range()),
+            (DefinitionKind::Parameter, 8..13)
+        );
+        // the parameters are not visible outside the declaration
+        let start = Position {
+            offset: 63,
+            line: 1,
+            column: 64,
+        };
+        assert_eq!(
+            errors,
+            vec![ResolveError {
+                kind: ResolveErrorKind::Undefined("nert".to_string()),
+                span: Span::new(start, start.advance("nert"))
+            }]
+        );
+        assert_eq!(errors[0].code(), "E0201");
+    }
+
+    #[test]
+    fn undefined() {
+        let (_, _, errors) =
+            resolve_source("lus jmk4'd jerldir. is jerldir'd xakant deln'c mea xerfo es snelyo.");
+        let undefined = errors
+            .iter()
+            .map(|err| (err.kind.clone(), err.span.range()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            undefined,
+            vec![
+                (ResolveErrorKind::Undefined("deln".to_string()), 40..44),
+                (ResolveErrorKind::Undefined("snelyo".to_string()), 60..66),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate() {
+        let (_, _, errors) = resolve_source(
+            "lus jmk4'd jerldir ad deln. deln es 1. iu es 2. nert ad nert'st es_tydivexy-o : nert mol.",
+        );
+        let duplicates = errors
+            .iter()
+            .map(|err| match &err.kind {
+                ResolveErrorKind::Duplicate { name, previous } => {
+                    (name.as_str(), err.span.range(), previous.range())
+                }
+                ResolveErrorKind::Undefined(_) => panic!("{err:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            duplicates,
+            vec![("deln", 28..32, 0..26), ("nert", 56..60, 48..52)]
+        );
+        assert_eq!(errors[0].code(), "E0202");
+    }
+}
@@ -0,0 +1,521 @@
+//! Verb signatures: which cases a verb takes in a procedure, how many nouns each of them
+//! lists, and which named parameters its `mea` clause may set.
+//!
+//! Signatures are written as a table, one verb per line:
+//!
+//! ```text
+//! # verb        cases                       named parameters
+//! laozia        lerj 2  el 2
+//! xes:window    lerj 1:number  el? 1        mea dejix:boolean
+//! ```
+//!
+//! Each case is followed by the number of nouns it takes: `2`, a range `1-3`, or `1+` for one
+//! or more. A case marked with `?` may be left out. Named parameters are spelled as in the
+//! source, e.g. `mea xerfo a'd b`. A `:type` after the verb, a number of nouns or a named
+//! parameter gives the type of the direct object, of the nouns of the case or of the value.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    parser::{Case, Procedure, Sentence, Verb},
+    span::Span,
+    token::{Reserved, TokenKind},
+    types::Type,
+};
+
+/// The verbs of the `jmk4` module.
+pub const JMK4_SIGNATURES: &str = "# verb           cases                          named parameters
+laozia:window    lerj 2:number  el 2:number
+is               'c 1
+lexisnain:window fal 1:number                   mea xerfo:number dejix:boolean
+xes:window       lerj 1:number  el 1:number     mea dejix:boolean
+";
+
+/// How many nouns a case takes: from `min` to `max`, or any number from `min` if `max` is `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Arity {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Arity {
+    #[must_use]
+    pub const fn exactly(n: usize) -> Self {
+        Self {
+            min: n,
+            max: Some(n),
+        }
+    }
+
+    /// Reads `2`, `1-3` or `1+`.
+    #[must_use]
+    pub fn parse(text: &str) -> Option<Self> {
+        if let Some(min) = text.strip_suffix('+') {
+            return Some(Self {
+                min: min.parse().ok()?,
+                max: None,
+            });
+        }
+        match text.split_once('-') {
+            Some((min, max)) => {
+                let (min, max) = (min.parse().ok()?, max.parse().ok()?);
+                (min <= max).then_some(Self {
+                    min,
+                    max: Some(max),
+                })
+            }
+            None => Some(Self::exactly(text.parse().ok()?)),
+        }
+    }
+
+    #[must_use]
+    pub fn contains(self, n: usize) -> bool {
+        self.min <= n && self.max.is_none_or(|max| n <= max)
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{max}"),
+            Some(max) => write!(f, "{}-{max}", self.min),
+            None => write!(f, "{}+", self.min),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CaseSignature {
+    pub case: Case,
+    pub arity: Arity,
+    pub required: bool,
+    // of each noun; `None` for any type
+    pub ty: Option<Type>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NamedParameterSignature {
+    // spelled as in the source, e.g. `xerfo` or `a'd b`
+    pub name: String,
+    pub ty: Option<Type>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VerbSignature {
+    pub verb: String,
+    // of the direct object; `None` for any type
+    pub object: Option<Type>,
+    pub cases: Vec<CaseSignature>,
+    pub named_parameters: Vec<NamedParameterSignature>,
+}
+
+// Splits `word:type` into `word` and the type
+fn split_type(word: &str) -> Option<(&str, Option<Type>)> {
+    match word.split_once(':') {
+        Some((word, ty)) => Some((word, Some(Type::parse(ty)?))),
+        None => Some((word, None)),
+    }
+}
+
+impl VerbSignature {
+    /// Reads one line of a signature table.
+    #[must_use]
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let (verb, object) = split_type(words.next()?)?;
+        let mut cases = vec![];
+        let mut named_parameters: Vec<String> = vec![];
+        while let Some(word) = words.next() {
+            if word == Reserved::Mea.as_str() {
+                // `a'd b` is one parameter
+                for word in words.by_ref() {
+                    match named_parameters.last_mut() {
+                        Some(last) if last.ends_with(Reserved::ApostropheD.as_str()) => {
+                            *last = format!("{last} {word}");
+                        }
+                        _ => named_parameters.push(word.to_string()),
+                    }
+                }
+                break;
+            }
+            let (case, required) = word
+                .strip_suffix('?')
+                .map_or((word, true), |case| (case, false));
+            let case = match TokenKind::from(case) {
+                TokenKind::Reserved(Reserved::Preposition(p)) => Case::Preposition(p),
+                TokenKind::Reserved(Reserved::ApostropheC) => Case::ApostropheC,
+                TokenKind::Reserved(Reserved::ApostropheI) => Case::ApostropheI,
+                _ => return None,
+            };
+            let (arity, ty) = split_type(words.next()?)?;
+            cases.push(CaseSignature {
+                case,
+                arity: Arity::parse(arity)?,
+                required,
+                ty,
+            });
+        }
+        Some(Self {
+            verb: verb.to_string(),
+            object,
+            cases,
+            named_parameters: named_parameters
+                .iter()
+                .map(|word| {
+                    let (name, ty) = split_type(word)?;
+                    Some(NamedParameterSignature {
+                        name: name.to_string(),
+                        ty,
+                    })
+                })
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    #[must_use]
+    pub fn case(&self, case: Case) -> Option<&CaseSignature> {
+        self.cases.iter().find(|signature| signature.case == case)
+    }
+
+    #[must_use]
+    pub fn named_parameter(&self, name: &str) -> Option<&NamedParameterSignature> {
+        self.named_parameters
+            .iter()
+            .find(|signature| signature.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SignatureErrorKind {
+    UnknownVerb(String),
+    CaseNotAllowed {
+        verb: String,
+        case: Case,
+    },
+    DuplicateCase {
+        verb: String,
+        case: Case,
+    },
+    Arity {
+        verb: String,
+        case: Case,
+        expected: Arity,
+        found: usize,
+    },
+    MissingCase {
+        verb: String,
+        case: Case,
+    },
+    NamedParameterNotAllowed {
+        verb: String,
+        name: String,
+    },
+}
+
+/// A procedure that does not match the signature of its verb.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SignatureError {
+    pub kind: SignatureErrorKind,
+    pub span: Span,
+}
+
+impl SignatureError {
+    /// A stable identifier of the kind of error, independent of the message wording.
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self.kind {
+            SignatureErrorKind::UnknownVerb(_) => "E0401",
+            SignatureErrorKind::CaseNotAllowed { .. } => "E0402",
+            SignatureErrorKind::DuplicateCase { .. } => "E0403",
+            SignatureErrorKind::Arity { .. } => "E0404",
+            SignatureErrorKind::MissingCase { .. } => "E0405",
+            SignatureErrorKind::NamedParameterNotAllowed { .. } => "E0406",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignatureRegistry {
+    verbs: HashMap<String, VerbSignature>,
+}
+
+impl SignatureRegistry {
+    /// Reads a signature table. Empty lines and lines starting with `#` are ignored.
+    ///
+    /// # Errors
+    /// Returns the (1-based) number of the first line that is not a valid signature.
+    pub fn from_table(table: &str) -> Result<Self, usize> {
+        let mut registry = Self::default();
+        for (i, line) in table.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            registry.insert(VerbSignature::parse(line).ok_or(i + 1)?);
+        }
+        Ok(registry)
+    }
+
+    /// The verbs of the `jmk4` module, from `JMK4_SIGNATURES`.
+    #[must_use]
+    pub fn jmk4() -> Self {
+        Self::from_table(JMK4_SIGNATURES).expect("JMK4_SIGNATURES is valid")
+    }
+
+    /// Adds `signature`, replacing any earlier one for the same verb.
+    pub fn insert(&mut self, signature: VerbSignature) {
+        self.verbs.insert(signature.verb.clone(), signature);
+    }
+
+    #[must_use]
+    pub fn get(&self, verb: &str) -> Option<&VerbSignature> {
+        self.verbs.get(verb)
+    }
+
+    /// Checks every procedure of `sentences`, including those of event handlers.
+    #[must_use]
+    pub fn check(&self, sentences: &[Sentence]) -> Vec<SignatureError> {
+        sentences
+            .iter()
+            .flat_map(|sentence| match sentence {
+                Sentence::Procedure(procedure) => std::slice::from_ref(procedure),
+                Sentence::EventHandler { procedures, .. }
+                | Sentence::GuardedEventHandler { procedures, .. } => procedures,
+                _ => &[],
+            })
+            .flat_map(|procedure| self.check_procedure(procedure))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn check_procedure(&self, procedure: &Procedure) -> Vec<SignatureError> {
+        let Verb(verb, verb_span) = &procedure.verb;
+        let Some(signature) = self.get(verb) else {
+            return vec![SignatureError {
+                kind: SignatureErrorKind::UnknownVerb(verb.to_string()),
+                span: *verb_span,
+            }];
+        };
+        let verb = verb.clone();
+        let mut errors = vec![];
+        let mut seen = vec![];
+        for nouns_with_case in &procedure.nouns_with_case_array {
+            let case = nouns_with_case.case;
+            let kind = match signature.case(case) {
+                None => SignatureErrorKind::CaseNotAllowed {
+                    verb: verb.to_string(),
+                    case,
+                },
+                Some(_) if seen.contains(&case) => SignatureErrorKind::DuplicateCase {
+                    verb: verb.to_string(),
+                    case,
+                },
+                Some(CaseSignature { arity, .. })
+                    if !arity.contains(nouns_with_case.nouns.len()) =>
+                {
+                    SignatureErrorKind::Arity {
+                        verb: verb.to_string(),
+                        case,
+                        expected: *arity,
+                        found: nouns_with_case.nouns.len(),
+                    }
+                }
+                Some(_) => {
+                    seen.push(case);
+                    continue;
+                }
+            };
+            seen.push(case);
+            errors.push(SignatureError {
+                kind,
+                span: nouns_with_case.span,
+            });
+        }
+        for missing in signature
+            .cases
+            .iter()
+            .filter(|signature| signature.required && !seen.contains(&signature.case))
+        {
+            errors.push(SignatureError {
+                kind: SignatureErrorKind::MissingCase {
+                    verb: verb.to_string(),
+                    case: missing.case,
+                },
+                span: procedure.span,
+            });
+        }
+        for mea_elem in procedure.mea_clause.iter().flatten() {
+            let name = mea_elem.named_parameter.to_string();
+            if signature.named_parameter(&name).is_none() {
+                errors.push(SignatureError {
+                    kind: SignatureErrorKind::NamedParameterNotAllowed {
+                        verb: verb.to_string(),
+                        name,
+                    },
+                    span: mea_elem.named_parameter.span,
+                });
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{messages::Locale, parse_program, token::Preposition};
+
+    fn check(source: &str) -> Vec<(SignatureErrorKind, std::ops::Range<usize>)> {
+        SignatureRegistry::jmk4()
+            .check(&parse_program(source).unwrap())
+            .into_iter()
+            .map(|err| (err.kind, err.span.range()))
+            .collect()
+    }
+
+    #[test]
+    fn selsurle() {
+        assert_eq!(check(include_str!("../selsurle.jmk4")), vec![]);
+    }
+
+    #[test]
+    fn table() {
+        let registry = SignatureRegistry::from_table(
+            "# comment\n\nxes:window lerj 1  el? 1-3:number 'i 2+ mea dejix a'd b:string\nis 'c 1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            registry.get("xes"),
+            Some(&VerbSignature {
+                verb: "xes".to_string(),
+                object: Some(Type::Window),
+                cases: vec![
+                    CaseSignature {
+                        case: Case::Preposition(Preposition::Lerj),
+                        arity: Arity::exactly(1),
+                        required: true,
+                        ty: None,
+                    },
+                    CaseSignature {
+                        case: Case::Preposition(Preposition::El),
+                        arity: Arity {
+                            min: 1,
+                            max: Some(3)
+                        },
+                        required: false,
+                        ty: Some(Type::Number),
+                    },
+                    CaseSignature {
+                        case: Case::ApostropheI,
+                        arity: Arity { min: 2, max: None },
+                        required: true,
+                        ty: None,
+                    },
+                ],
+                named_parameters: vec![
+                    NamedParameterSignature {
+                        name: "dejix".to_string(),
+                        ty: None,
+                    },
+                    NamedParameterSignature {
+                        name: "a'd b".to_string(),
+                        ty: Some(Type::String),
+                    },
+                ],
+            })
+        );
+        assert_eq!(SignatureRegistry::from_table("is 'c 1\nxes lerj\n"), Err(2));
+        assert_eq!(SignatureRegistry::from_table("xes mal 1"), Err(1));
+        assert_eq!(SignatureRegistry::from_table("xes el 3-1"), Err(1));
+        assert_eq!(SignatureRegistry::from_table("xes:colour el 1"), Err(1));
+    }
+
+    #[test]
+    fn arity() {
+        let arity = Arity::parse("1-3").unwrap();
+        assert!(!arity.contains(0) && arity.contains(3) && !arity.contains(4));
+        assert!(Arity::parse("2+").unwrap().contains(100));
+        assert_eq!(
+            ["2", "1-3", "2+"].map(|a| Arity::parse(a).unwrap().to_string()),
+            ["2", "1-3", "2+"]
+        );
+    }
+
+    #[test]
+    fn mismatches() {
+        let lerj = Case::Preposition(Preposition::Lerj);
+        assert_eq!(
+            check("laozia jerldir lerj 10 el 168 ad 218."),
+            vec![(
+                SignatureErrorKind::Arity {
+                    verb: "laozia".to_string(),
+                    case: lerj,
+                    expected: Arity::exactly(2),
+                    found: 1,
+                },
+                15..22
+            )]
+        );
+        assert_eq!(
+            check("xes jerldir lerj a el b fal c lerj d mea dejix es deln mal xerfo es 1."),
+            vec![
+                (
+                    SignatureErrorKind::CaseNotAllowed {
+                        verb: "xes".to_string(),
+                        case: Case::Preposition(Preposition::Fal),
+                    },
+                    24..29
+                ),
+                (
+                    SignatureErrorKind::DuplicateCase {
+                        verb: "xes".to_string(),
+                        case: lerj,
+                    },
+                    30..36
+                ),
+                (
+                    SignatureErrorKind::NamedParameterNotAllowed {
+                        verb: "xes".to_string(),
+                        name: "xerfo".to_string(),
+                    },
+                    59..64
+                ),
+            ]
+        );
+        assert_eq!(
+            check("elx shrlo laozia jerldir el 1 ad 2 melx shrlo nert jerldir."),
+            vec![
+                (
+                    SignatureErrorKind::MissingCase {
+                        verb: "laozia".to_string(),
+                        case: lerj,
+                    },
+                    10..34
+                ),
+                (SignatureErrorKind::UnknownVerb("nert".to_string()), 46..50),
+            ]
+        );
+    }
+
+    #[test]
+    fn messages() {
+        let err = SignatureError {
+            kind: SignatureErrorKind::Arity {
+                verb: "laozia".to_string(),
+                case: Case::Preposition(Preposition::Lerj),
+                expected: Arity::exactly(2),
+                found: 1,
+            },
+            span: Span::default(),
+        };
+        assert_eq!(
+            Locale::Japanese.signature_error_message(&err.kind),
+            "`laozia` の `lerj` には名詞が 2 個必要ですが 1 個あります"
+        );
+        assert_eq!(
+            Locale::English.signature_error_message(&err.kind),
+            "`lerj` of `laozia` takes 2 nouns, found 1"
+        );
+        assert_eq!(err.code(), "E0404");
+    }
+}
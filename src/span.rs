@@ -0,0 +1,124 @@
+use std::ops::Range;
+
+/// A location in the source. `offset` is a byte offset, `line` and `column` are 1-based
+/// and counted in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl Position {
+    /// The position right after `c`.
+    #[must_use]
+    pub const fn advance_char(self, c: char) -> Self {
+        if c == '\n' {
+            Self {
+                offset: self.offset + c.len_utf8(),
+                line: self.line + 1,
+                column: 1,
+            }
+        } else {
+            Self {
+                offset: self.offset + c.len_utf8(),
+                line: self.line,
+                column: self.column + 1,
+            }
+        }
+    }
+
+    /// The position `self` would have if the text it is counted in came right after `base`.
+    #[must_use]
+    pub const fn offset_by(self, base: Self) -> Self {
+        Self {
+            offset: base.offset + self.offset,
+            line: base.line + self.line - 1,
+            column: if self.line == 1 {
+                base.column + self.column - 1
+            } else {
+                self.column
+            },
+        }
+    }
+
+    /// The position right after `text`.
+    #[must_use]
+    pub fn advance(self, text: &str) -> Self {
+        text.chars().fold(self, Self::advance_char)
+    }
+}
+
+/// A half-open range `start..end` in the source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    #[must_use]
+    pub const fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+
+    /// The span covering both `self` and `other`, which must come later in the source.
+    #[must_use]
+    pub const fn to(self, other: Self) -> Self {
+        Self {
+            start: self.start,
+            end: other.end,
+        }
+    }
+
+    /// Splits the span right after `prefix`, which must be the beginning of the spanned text.
+    #[must_use]
+    pub fn split_at(self, prefix: &str) -> (Self, Self) {
+        let mid = self.start.advance(prefix);
+        (Self::new(self.start, mid), Self::new(mid, self.end))
+    }
+
+    /// See `Position::offset_by`.
+    #[must_use]
+    pub const fn offset_by(self, base: Position) -> Self {
+        Self::new(self.start.offset_by(base), self.end.offset_by(base))
+    }
+
+    #[must_use]
+    pub const fn range(self) -> Range<usize> {
+        self.start.offset..self.end.offset
+    }
+}
+
+/// Syntax that can be compared for structure alone.
+///
+/// `a.without_spans() == b.without_spans()` holds when `a` and `b` only differ in where they
+/// are in the source, and in the ids the parser gave their nouns.
+pub trait WithoutSpans {
+    /// A copy of `self` with every span replaced by `Span::default()`, and every noun id by
+    /// `NounId::default()`.
+    #[must_use]
+    fn without_spans(&self) -> Self;
+}
+
+impl<T: WithoutSpans> WithoutSpans for Vec<T> {
+    fn without_spans(&self) -> Self {
+        self.iter().map(WithoutSpans::without_spans).collect()
+    }
+}
+
+impl<T: WithoutSpans> WithoutSpans for Option<T> {
+    fn without_spans(&self) -> Self {
+        self.as_ref().map(WithoutSpans::without_spans)
+    }
+}
@@ -0,0 +1,323 @@
+//! Lexing input that arrives piece by piece, e.g. from a pipe or a socket.
+//!
+//! Chunks may be cut anywhere, even inside a string literal or between a word and its suffix:
+//! only the text up to the last whitespace outside literals and comments is lexed, and the rest
+//! waits for the next chunk.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Read},
+};
+
+use crate::{
+    span::Position,
+    symbol::Interner,
+    token::Token,
+    tokenize::{tokenize_partial, tokenize_with, LexError, LexerConfig},
+};
+
+/// A lexer that is fed chunks of the source and hands out tokens as soon as they are complete.
+///
+/// Spans are relative to the whole stream. After an error the lexer should not be fed any more.
+#[derive(Debug, Clone)]
+pub struct StreamingLexer {
+    config: LexerConfig,
+    // the text fed but not lexed yet
+    buffer: String,
+    // where `buffer` starts in the stream
+    start: Position,
+    tokens: VecDeque<Token>,
+    interner: Interner,
+}
+
+impl StreamingLexer {
+    #[must_use]
+    pub fn new(config: LexerConfig) -> Self {
+        Self {
+            config,
+            buffer: String::new(),
+            start: Position::default(),
+            tokens: VecDeque::new(),
+            interner: Interner::new(),
+        }
+    }
+
+    /// Appends `chunk` to the input and lexes as much of it as is sure to be complete.
+    ///
+    /// # Errors
+    /// Returns a `LexError` found in the part that has been lexed.
+    pub fn feed(&mut self, chunk: &str) -> Result<(), LexError> {
+        self.buffer.push_str(chunk);
+        let start = self.start;
+        let (words, end) =
+            tokenize_partial(&self.buffer, &self.config).map_err(|err| LexError {
+                span: err.span.offset_by(start),
+                ..err
+            })?;
+        for (word, span) in words {
+            let token = Token::from_word(
+                word,
+                span.offset_by(start),
+                &self.config,
+                &mut self.interner,
+            );
+            self.tokens.push_back(token);
+        }
+        self.start = self.start.advance(&self.buffer[..end]);
+        self.buffer.drain(..end);
+        Ok(())
+    }
+
+    /// Marks the end of the input and lexes what is left of it.
+    ///
+    /// # Errors
+    /// Returns a `LexError`, e.g. for a string literal that is still open.
+    pub fn finish(&mut self) -> Result<(), LexError> {
+        let start = self.start;
+        let words = tokenize_with(&self.buffer, &self.config).map_err(|err| LexError {
+            span: err.span.offset_by(start),
+            ..err
+        })?;
+        for (word, span) in words {
+            let token = Token::from_word(
+                word,
+                span.offset_by(start),
+                &self.config,
+                &mut self.interner,
+            );
+            self.tokens.push_back(token);
+        }
+        self.start = self.start.advance(&self.buffer);
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// The next token lexed so far, if any.
+    pub fn next_token(&mut self) -> Option<Token> {
+        self.tokens.pop_front()
+    }
+}
+
+#[derive(Debug)]
+pub enum StreamError {
+    Io(io::Error),
+    Lex(LexError),
+}
+
+impl From<io::Error> for StreamError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<LexError> for StreamError {
+    fn from(err: LexError) -> Self {
+        Self::Lex(err)
+    }
+}
+
+/// Lexes everything `reader` yields, reading only as far as needed for the next token.
+/// Input that is not UTF-8 is an `io::ErrorKind::InvalidData` error.
+pub fn tokenize_reader<R: Read>(reader: R, config: LexerConfig) -> ReadTokens<R> {
+    ReadTokens {
+        reader,
+        lexer: StreamingLexer::new(config),
+        incomplete: vec![],
+        done: false,
+    }
+}
+
+/// The iterator returned by `tokenize_reader`. It stops after the first error.
+#[derive(Debug)]
+pub struct ReadTokens<R> {
+    reader: R,
+    lexer: StreamingLexer,
+    // the start of a character cut off by the previous read
+    incomplete: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> ReadTokens<R> {
+    fn read_more(&mut self) -> Result<(), StreamError> {
+        let mut buf = [0; 4096];
+        let len = loop {
+            match self.reader.read(&mut buf) {
+                Ok(len) => break len,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        };
+        if len == 0 {
+            self.done = true;
+            if !self.incomplete.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not end at a character boundary",
+                )
+                .into());
+            }
+            return Ok(self.lexer.finish()?);
+        }
+        self.incomplete.extend_from_slice(&buf[..len]);
+        let valid = match std::str::from_utf8(&self.incomplete) {
+            Ok(text) => text.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err).into()),
+        };
+        let text = std::str::from_utf8(&self.incomplete[..valid]).expect("checked above");
+        self.lexer.feed(text)?;
+        self.incomplete.drain(..valid);
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for ReadTokens<R> {
+    type Item = Result<Token, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(token) = self.lexer.next_token() {
+                return Some(Ok(token));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(err) = self.read_more() {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        token::{tokenize, TokenKind},
+        tokenize::LexErrorKind,
+    };
+
+    fn summary(tokens: &[Token]) -> Vec<(TokenKind, std::ops::Range<usize>, usize, usize)> {
+        tokens
+            .iter()
+            .map(|t| {
+                (
+                    t.kind.clone(),
+                    t.span.range(),
+                    t.span.start.line,
+                    t.span.start.column,
+                )
+            })
+            .collect()
+    }
+
+    fn feed_all(chunks: &[&str]) -> Result<Vec<Token>, LexError> {
+        let mut lexer = StreamingLexer::new(LexerConfig::default());
+        let mut tokens = vec![];
+        for chunk in chunks {
+            lexer.feed(chunk)?;
+            tokens.extend(std::iter::from_fn(|| lexer.next_token()));
+        }
+        lexer.finish()?;
+        tokens.extend(std::iter::from_fn(|| lexer.next_token()));
+        Ok(tokens)
+    }
+
+    // hands out at most `chunk` bytes per read
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.chunk.min(self.bytes.len()).min(buf.len());
+            buf[..len].copy_from_slice(&self.bytes[..len]);
+            self.bytes = &self.bytes[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn split_anywhere() {
+        let source = include_str!("../selsurle.jmk4");
+        let expected = summary(&tokenize(source).unwrap());
+        for (i, _) in source.char_indices() {
+            let tokens = feed_all(&[&source[..i], &source[i..]]).unwrap();
+            assert_eq!(summary(&tokens), expected, "split at {i}");
+        }
+    }
+
+    #[test]
+    fn literal_and_suffix_across_chunks() {
+        let tokens = feed_all(&[
+            "is selsurle <iu ",
+            "es\\> ",
+            "jm>'",
+            "c. ker",
+            "numesaxm'",
+            "st",
+        ])
+        .unwrap();
+        let source = "is selsurle <iu es\\> jm>'c. kernumesaxm'st";
+        assert_eq!(summary(&tokens), summary(&tokenize(source).unwrap()));
+        assert_eq!(tokens.len(), 7);
+    }
+
+    #[test]
+    fn tokens_are_handed_out_early() {
+        let mut lexer = StreamingLexer::new(LexerConfig::default());
+        lexer.feed("selsurle es i").unwrap();
+        assert_eq!(
+            std::iter::from_fn(|| lexer.next_token()).count(),
+            2,
+            "`i` may go on"
+        );
+        lexer.feed("u.").unwrap();
+        assert_eq!(lexer.next_token(), None);
+        lexer.finish().unwrap();
+        assert_eq!(std::iter::from_fn(|| lexer.next_token()).count(), 2);
+    }
+
+    #[test]
+    fn errors() {
+        let err = feed_all(&["selsurle es <i", "u.\n"]).unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::UnterminatedStringLiteral);
+        assert_eq!(err.span.range(), 12..17);
+        let err = feed_all(&["selsurle es\niu", " A"]).unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::IllegalCharacter('A'));
+        assert_eq!((err.span.range(), err.span.start.line), (15..16, 2));
+    }
+
+    #[test]
+    fn reader() {
+        let source = "φasa es <жβ>. # é\nis φasa ж'c.";
+        let expected = summary(&tokenize(source).unwrap());
+        for chunk in 1..8 {
+            let tokens = tokenize_reader(
+                Trickle {
+                    bytes: source.as_bytes(),
+                    chunk,
+                },
+                LexerConfig::default(),
+            )
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+            assert_eq!(summary(&tokens), expected);
+        }
+    }
+
+    #[test]
+    fn reader_errors() {
+        let mut tokens = tokenize_reader(&b"iu \xff iu"[..], LexerConfig::default());
+        assert!(matches!(
+            tokens.next(),
+            Some(Err(StreamError::Io(err))) if err.kind() == io::ErrorKind::InvalidData
+        ));
+        assert!(tokens.next().is_none());
+
+        let tokens = tokenize_reader(&b"iu <iu"[..], LexerConfig::default()).collect::<Vec<_>>();
+        assert!(matches!(tokens[..], [Ok(_), Err(StreamError::Lex(_))]));
+    }
+}
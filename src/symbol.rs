@@ -0,0 +1,120 @@
+//! Shared strings, so that tokens are cheap to clone and compare.
+//!
+//! An `Interner` belongs to the lexing of one source, and stores each distinct spelling in it
+//! once. A symbol is a reference-counted string rather than an index into the interner, so it
+//! reads without the interner and outlives it; cloning one only bumps the count. A string is
+//! freed with the last token or tree referring to it.
+
+use std::{
+    borrow::Borrow,
+    collections::HashSet,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    sync::Arc,
+};
+
+/// A string shared between the tokens spelled the same.
+#[derive(Clone, Eq)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        // symbols from the same interner are equal exactly when they share the string
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+// the same as for `str`, so that a set of symbols can be searched by `&str`
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(string: &str) -> Self {
+        Self(string.into())
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The symbols of one source.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    symbols: HashSet<Symbol>,
+}
+
+impl Interner {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, string: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(string) {
+            return symbol.clone();
+        }
+        let symbol = Symbol::from(string);
+        self.symbols.insert(symbol.clone());
+        symbol
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intern() {
+        let mut interner = Interner::new();
+        let selsurle = interner.intern("selsurle");
+        let owned = String::from("selsur") + "le";
+        let again = interner.intern(&owned);
+        assert_eq!(selsurle, again);
+        assert!(Arc::ptr_eq(&selsurle.0, &again.0));
+        assert_ne!(selsurle, interner.intern("selsurl"));
+        assert_eq!(selsurle, Symbol::from("selsurle"));
+        assert_eq!(selsurle.as_str(), "selsurle");
+        assert_eq!(selsurle, "selsurle");
+        assert_eq!(format!("{selsurle} {selsurle:?}"), "selsurle \"selsurle\"");
+    }
+}
@@ -1,3 +1,12 @@
+use std::fmt;
+
+use crate::{
+    span::{Span, WithoutSpans},
+    symbol::{Interner, Symbol},
+    tokenize::{unescape, unescape_with, LexError, LexerConfig},
+    transliterate::Transliteration,
+};
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum Reserved {
     Preposition(Preposition),
@@ -31,65 +40,380 @@ pub enum Preposition {
     Cecioj,
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+impl Reserved {
+    /// How the reserved word is spelled in the source.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Preposition(p) => p.as_str(),
+            Self::ApostropheD => "'d",
+            Self::ApostropheC => "'c",
+            Self::ApostropheI => "'i",
+            Self::ApostropheSt => "'st",
+            Self::Adit => "adit",
+            Self::Ad => "ad",
+            Self::PunctuationComma => ",",
+            Self::PunctuationPeriod => ".",
+            Self::PunctuationColon => ":",
+            Self::Elx => "elx",
+            Self::Shrlo => "shrlo",
+            Self::Melx => "melx",
+            Self::Felx => "felx",
+            Self::Mea => "mea",
+            Self::Mal => "mal",
+            Self::Es => "es",
+            Self::Lus => "lus",
+            Self::Io => "io",
+            Self::HyphenO => "-o",
+            Self::HyphenIl => "-il",
+        }
+    }
+}
+
+impl Reserved {
+    /// The reserved word spelled `word`, if it is one.
+    #[must_use]
+    pub fn from_word(word: &str) -> Option<Self> {
+        Some(match word {
+            "el" => Self::Preposition(Preposition::El),
+            "lerj" => Self::Preposition(Preposition::Lerj),
+            "fal" => Self::Preposition(Preposition::Fal),
+            "cecioj" => Self::Preposition(Preposition::Cecioj),
+            "'d" => Self::ApostropheD,
+            "'c" => Self::ApostropheC,
+            "'i" => Self::ApostropheI,
+            "'st" => Self::ApostropheSt,
+            "adit" => Self::Adit,
+            "ad" => Self::Ad,
+            "," => Self::PunctuationComma,
+            "." => Self::PunctuationPeriod,
+            ":" => Self::PunctuationColon,
+            "elx" => Self::Elx,
+            "shrlo" => Self::Shrlo,
+            "melx" => Self::Melx,
+            "felx" => Self::Felx,
+            "mea" => Self::Mea,
+            "mal" => Self::Mal,
+            "es" => Self::Es,
+            "lus" => Self::Lus,
+            "io" => Self::Io,
+            "-o" => Self::HyphenO,
+            "-il" => Self::HyphenIl,
+            _ => return None,
+        })
+    }
+}
+
+impl Preposition {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::El => "el",
+            Self::Lerj => "lerj",
+            Self::Fal => "fal",
+            Self::Cecioj => "cecioj",
+        }
+    }
+}
+
+/// A decimal number, `mantissa` × 10^-`scale`. `1.50` is kept as 150 × 10^-2.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Number {
+    pub mantissa: i64,
+    pub scale: u32,
+}
+
+impl Number {
+    #[must_use]
+    pub const fn integer(value: i64) -> Self {
+        Self {
+            mantissa: value,
+            scale: 0,
+        }
+    }
+
+    /// Reads a number literal, `"-"? digit+ ("." digit+)?`. The point is `.` under every
+    /// `LexerConfig`, so that a number reads and prints the same whatever the punctuation.
+    /// Returns `None` if `text` is not one, or if its digits do not fit in an `i64`.
+    #[must_use]
+    pub fn parse(text: &str) -> Option<Self> {
+        if !is_number_literal(text) {
+            return None;
+        }
+        let (integer_part, fraction) = text.split_once('.').unwrap_or((text, ""));
+        Some(Self {
+            mantissa: format!("{integer_part}{fraction}").parse().ok()?,
+            scale: u32::try_from(fraction.len()).ok()?,
+        })
+    }
+
+    #[must_use]
+    pub const fn is_integer(self) -> bool {
+        self.scale == 0
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_wrap)]
+    pub fn to_f64(self) -> f64 {
+        self.mantissa as f64 / 10_f64.powi(self.scale as i32)
+    }
+}
 
-pub enum Token {
-    StringLiteral { literal: String },
-    NormalIdent { ident: String },
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.scale == 0 {
+            return write!(f, "{}", self.mantissa);
+        }
+        let scale = self.scale as usize;
+        let digits = format!(
+            "{:0>width$}",
+            self.mantissa.unsigned_abs(),
+            width = scale + 1
+        );
+        let (integer_part, fraction) = digits.split_at(digits.len() - scale);
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        write!(f, "{sign}{integer_part}.{fraction}")
+    }
+}
+
+// "-"? digit+ ("." digit+)?
+pub fn is_number_literal(text: &str) -> bool {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    match digits.split_once('.') {
+        Some((integer_part, fraction)) => is_digits(integer_part) && is_digits(fraction),
+        None => is_digits(digits),
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum TokenKind {
+    // `literal` is the source text with its `<` and `>`, `value` the decoded contents
+    StringLiteral { literal: Symbol, value: Symbol },
+    NormalIdent { ident: Symbol },
+    Number { number: Number },
     Reserved(Reserved),
 }
 
-impl Token {
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl AsRef<Self> for Token {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+impl WithoutSpans for Token {
+    fn without_spans(&self) -> Self {
+        Self {
+            kind: self.kind.clone(),
+            span: Span::default(),
+        }
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StringLiteral { literal, .. } => f.write_str(literal.as_str()),
+            Self::NormalIdent { ident } => f.write_str(ident.as_str()),
+            Self::Number { number } => write!(f, "{number}"),
+            Self::Reserved(reserved) => f.write_str(reserved.as_str()),
+        }
+    }
+}
+
+impl TokenKind {
+    #[must_use]
     pub fn from(tok: &str) -> Self {
-        match tok {
-            "el" => Self::Reserved(Reserved::Preposition(Preposition::El)),
-            "lerj" => Self::Reserved(Reserved::Preposition(Preposition::Lerj)),
-            "fal" => Self::Reserved(Reserved::Preposition(Preposition::Fal)),
-            "cecioj" => Self::Reserved(Reserved::Preposition(Preposition::Cecioj)),
-            "'d" => Self::Reserved(Reserved::ApostropheD),
-            "'c" => Self::Reserved(Reserved::ApostropheC),
-            "'i" => Self::Reserved(Reserved::ApostropheI),
-            "'st" => Self::Reserved(Reserved::ApostropheSt),
-            "adit" => Self::Reserved(Reserved::Adit),
-            "ad" => Self::Reserved(Reserved::Ad),
-            "," => Self::Reserved(Reserved::PunctuationComma),
-            "." => Self::Reserved(Reserved::PunctuationPeriod),
-            ":" => Self::Reserved(Reserved::PunctuationColon),
-            "elx" => Self::Reserved(Reserved::Elx),
-            "shrlo" => Self::Reserved(Reserved::Shrlo),
-            "melx" => Self::Reserved(Reserved::Melx),
-            "felx" => Self::Reserved(Reserved::Felx),
-            "mea" => Self::Reserved(Reserved::Mea),
-            "mal" => Self::Reserved(Reserved::Mal),
-            "es" => Self::Reserved(Reserved::Es),
-            "lus" => Self::Reserved(Reserved::Lus),
-            "io" => Self::Reserved(Reserved::Io),
-            "-o" => Self::Reserved(Reserved::HyphenO),
-            "-il" => Self::Reserved(Reserved::HyphenIl),
-            tok if tok.starts_with('<') && tok.ends_with('>') => Self::StringLiteral {
-                literal: tok.to_string(),
-            },
-            tok => Self::NormalIdent {
-                ident: tok.to_string(),
-            },
+        if let Some(number) = Number::parse(tok) {
+            return Self::Number { number };
+        }
+        if let Some(reserved) = Reserved::from_word(tok) {
+            return Self::Reserved(reserved);
         }
+        if tok.starts_with('<') && tok.ends_with('>') {
+            return Self::StringLiteral {
+                literal: Symbol::from(tok),
+                // `tokenize` has already rejected bad escapes
+                value: Symbol::from(&*unescape(tok).unwrap_or_default()),
+            };
+        }
+        Self::NormalIdent {
+            ident: Symbol::from(tok),
+        }
+    }
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, LexError> {
+    tokenize_with(input, &LexerConfig::default())
+}
+
+/// Tokenizes `input` after rewriting it with `transliteration`.
+/// The spans of the tokens, and of an error, point at `input` itself.
+pub fn tokenize_transliterated(
+    input: &str,
+    transliteration: &Transliteration,
+    config: &LexerConfig,
+) -> Result<Vec<Token>, LexError> {
+    let transliterated = transliteration.apply(input, config);
+    match tokenize_with(&transliterated.text, config) {
+        Ok(tokens) => Ok(tokens
+            .into_iter()
+            .map(|tok| Token {
+                span: transliterated.original_span(tok.span),
+                ..tok
+            })
+            .collect()),
+        Err(err) => Err(LexError {
+            span: transliterated.original_span(err.span),
+            ..err
+        }),
     }
 }
 
-pub fn tokenize(input: &str) -> Vec<Token> {
-    super::tokenize::tokenize(input)
+pub fn tokenize_with(input: &str, config: &LexerConfig) -> Result<Vec<Token>, LexError> {
+    let mut interner = Interner::new();
+    Ok(super::tokenize::tokenize_with(input, config)?
+        .into_iter()
+        .map(|(word, span)| Token::from_word(word, span, config, &mut interner))
+        .collect())
+}
+
+/// Same as `tokenize_with`, but goes on after an error like
+/// `tokenize::tokenize_with_recovery`. Returns the tokens and every error, in source order.
+#[must_use]
+pub fn tokenize_with_recovery(input: &str, config: &LexerConfig) -> (Vec<Token>, Vec<LexError>) {
+    let mut interner = Interner::new();
+    let (words, errors) = super::tokenize::tokenize_with_recovery(input, config);
+    let tokens = words
         .into_iter()
-        .map(|s| Token::from(&s))
-        .collect()
+        .map(|(word, span)| Token::from_word(word, span, config, &mut interner))
+        .collect();
+    (tokens, errors)
+}
+
+impl Token {
+    // `word` must be one that `tokenize` has accepted. Identifiers and string literals are
+    // shared through `interner`; nothing is allocated for a spelling seen before.
+    #[must_use]
+    pub fn from_word(
+        word: &str,
+        span: Span,
+        config: &LexerConfig,
+        interner: &mut Interner,
+    ) -> Self {
+        let mut chars = word.chars();
+        let punctuation = match (chars.next(), chars.next()) {
+            (Some(c), None) => config.punctuation(c),
+            _ => None,
+        };
+        if let Some(reserved) = punctuation.or_else(|| Reserved::from_word(word)) {
+            return Self {
+                kind: TokenKind::Reserved(reserved),
+                span,
+            };
+        }
+        if let Some(number) = Number::parse(word) {
+            return Self {
+                kind: TokenKind::Number { number },
+                span,
+            };
+        }
+        let kind = if word.starts_with(config.string_literal_open) {
+            TokenKind::StringLiteral {
+                literal: interner.intern(word),
+                value: interner.intern(&unescape_with(word, config).unwrap_or_default()),
+            }
+        } else {
+            TokenKind::NormalIdent {
+                ident: interner.intern(word),
+            }
+        };
+        Self { kind, span }
+    }
 }
 
 #[test]
 fn parsing_primary_noun() {
-    let tokens = tokenize("xakant");
+    let tokens = tokenize("xakant").unwrap();
+    let start = crate::span::Position::default();
     assert_eq!(
         tokens,
-        vec![Token::NormalIdent {
-            ident: "xakant".to_string()
+        vec![Token {
+            kind: TokenKind::NormalIdent {
+                ident: Symbol::from("xakant")
+            },
+            span: Span::new(start, start.advance("xakant"))
         }]
     );
 }
+
+#[test]
+fn interned() {
+    let tokens = tokenize("<a> xakant <a> xakant").unwrap();
+    let texts = tokens
+        .iter()
+        .map(|tok| match &tok.kind {
+            TokenKind::StringLiteral { literal, .. } => literal.as_str(),
+            TokenKind::NormalIdent { ident } => ident.as_str(),
+            _ => unreachable!(),
+        })
+        .collect::<Vec<_>>();
+    assert!(std::ptr::eq(texts[0], texts[2]));
+    assert!(std::ptr::eq(texts[1], texts[3]));
+}
+
+#[test]
+fn number() {
+    let kinds = |input| {
+        tokenize(input)
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.kind)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        kinds("10 -3 1.50 jmk4 -o."),
+        vec![
+            TokenKind::Number {
+                number: Number::integer(10)
+            },
+            TokenKind::Number {
+                number: Number::integer(-3)
+            },
+            TokenKind::Number {
+                number: Number {
+                    mantissa: 150,
+                    scale: 2
+                }
+            },
+            TokenKind::NormalIdent {
+                ident: Symbol::from("jmk4")
+            },
+            TokenKind::Reserved(Reserved::HyphenO),
+            TokenKind::Reserved(Reserved::PunctuationPeriod),
+        ]
+    );
+    let number = |text| Number::parse(text).unwrap();
+    assert_eq!(number("1.50").to_string(), "1.50");
+    assert_eq!(number("-0.05").to_string(), "-0.05");
+    assert_eq!(number("-12").to_string(), "-12");
+    assert!((number("-2.5").to_f64() + 2.5).abs() < f64::EPSILON);
+    assert!(number("7").is_integer());
+    for text in [
+        "-",
+        "1.",
+        ".5",
+        "1.2.3",
+        "4abc",
+        "--1",
+        "99999999999999999999",
+    ] {
+        assert_eq!(Number::parse(text), None, "{text}");
+    }
+}
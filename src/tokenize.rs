@@ -1,3 +1,10 @@
+use std::{borrow::Cow, ops::Range};
+
+use crate::{
+    span::{Position, Span},
+    token::{is_number_literal, Number, Reserved},
+};
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 enum CharKind {
     WordConstituent,
@@ -5,122 +12,511 @@ enum CharKind {
     SimplePunctuation,
     StartsStringLiteral,
     EndsStringLiteral,
+    StartsComment,
+}
+
+/// Which characters may make up a word.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Alphabet {
+    /// Exactly the listed characters.
+    Chars(Vec<char>),
+    /// Every `XID_Continue` character, plus `'` and `-` needed by suffixes such as `'d` and `-il`.
+    UnicodeXid,
+}
+
+impl Alphabet {
+    #[must_use]
+    pub fn contains(&self, c: char) -> bool {
+        match self {
+            Self::Chars(chars) => chars.contains(&c),
+            Self::UnicodeXid => unicode_ident::is_xid_continue(c) || matches!(c, '\'' | '-'),
+        }
+    }
+}
+
+/// The characters the lexer accepts outside string literals, and what they mean.
+///
+/// A character is looked up in this order: the literal delimiters, the punctuation,
+/// `alphabet`, whitespace, and `#` which starts a comment. Anything else is illegal.
+/// The decimal point of a number is always `.`, which is accepted between digits even when it
+/// is not punctuation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LexerConfig {
+    pub alphabet: Alphabet,
+    // the punctuation ending a sentence, separating nouns and introducing a condition;
+    // each is a word by itself
+    pub period: char,
+    pub comma: char,
+    pub colon: char,
+    pub string_literal_open: char,
+    pub string_literal_close: char,
+}
+
+impl Default for LexerConfig {
+    /// The romanised alphabet `selsurle.jmk4` is written in.
+    fn default() -> Self {
+        let mut alphabet = ('a'..='z').chain('0'..='9').collect::<Vec<_>>();
+        alphabet.extend(['φ', 'β', 'ж', '\'', '-', '_']);
+        Self {
+            alphabet: Alphabet::Chars(alphabet),
+            period: '.',
+            comma: ',',
+            colon: ':',
+            string_literal_open: '<',
+            string_literal_close: '>',
+        }
+    }
+}
+
+impl LexerConfig {
+    /// Accepts any Unicode identifier character in a word, e.g. a native script.
+    #[must_use]
+    pub fn unicode_xid() -> Self {
+        Self {
+            alphabet: Alphabet::UnicodeXid,
+            ..Self::default()
+        }
+    }
+
+    /// The punctuation `c` stands for, if any.
+    #[must_use]
+    pub const fn punctuation(&self, c: char) -> Option<Reserved> {
+        if c == self.period {
+            Some(Reserved::PunctuationPeriod)
+        } else if c == self.comma {
+            Some(Reserved::PunctuationComma)
+        } else if c == self.colon {
+            Some(Reserved::PunctuationColon)
+        } else {
+            None
+        }
+    }
+
+    // `None` for a character that may only appear inside a string literal
+    fn classify(&self, c: char) -> Option<CharKind> {
+        if c == self.string_literal_open {
+            Some(CharKind::StartsStringLiteral)
+        } else if c == self.string_literal_close {
+            Some(CharKind::EndsStringLiteral)
+        } else if self.punctuation(c).is_some() {
+            Some(CharKind::SimplePunctuation)
+        } else if self.alphabet.contains(c) {
+            Some(CharKind::WordConstituent)
+        } else if c.is_whitespace() {
+            Some(CharKind::Space)
+        } else if c == '#' {
+            Some(CharKind::StartsComment)
+        } else {
+            None
+        }
+    }
 }
 
-fn classify_char(c: char) -> CharKind {
-    match c {
-        'a'..='z' | 'φ' | 'β' | 'ж' | '0'..='9' | '\'' | '-' | '_' => CharKind::WordConstituent,
-        c if c.is_whitespace() => CharKind::Space,
-        '.' | ',' | ':' => CharKind::SimplePunctuation,
-        '<' => CharKind::StartsStringLiteral,
-        '>' => CharKind::EndsStringLiteral,
-        _ => panic!("unknown character {c}"),
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LexErrorKind {
+    IllegalCharacter(char),
+    UnmatchedClosingBracket,
+    UnterminatedStringLiteral,
+    UnterminatedBlockComment,
+    NumberOutOfRange,
+    InvalidEscape,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+}
+
+impl LexError {
+    /// A stable identifier of the kind of error, independent of the message wording.
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self.kind {
+            LexErrorKind::IllegalCharacter(_) => "E0101",
+            LexErrorKind::UnmatchedClosingBracket => "E0102",
+            LexErrorKind::UnterminatedStringLiteral => "E0103",
+            LexErrorKind::UnterminatedBlockComment => "E0104",
+            LexErrorKind::NumberOutOfRange => "E0105",
+            LexErrorKind::InvalidEscape => "E0106",
+        }
     }
 }
 
-pub fn tokenize(input: &str) -> Vec<String> {
-    let input = format!("{input} ");
-    to_words(&input)
+pub fn tokenize_with<'a>(
+    input: &'a str,
+    config: &LexerConfig,
+) -> Result<Vec<(&'a str, Span)>, LexError> {
+    let mut errors = vec![];
+    let words = check_words(to_words(input, config)?, config, &mut errors);
+    first_error(words, errors)
+}
+
+/// Same as `tokenize_with`, but goes on after an error: a character that cannot be lexed is
+/// skipped, and a malformed number or string literal is dropped.
+///
+/// Returns the words that could be lexed and every error, in source order.
+pub fn tokenize_with_recovery<'a>(
+    input: &'a str,
+    config: &LexerConfig,
+) -> (Vec<(&'a str, Span)>, Vec<LexError>) {
+    let mut errors = vec![];
+    let (words, _) = scan(input, config, false, &mut errors);
+    let words = check_words(words, config, &mut errors);
+    errors.sort_by_key(|err| err.span.start.offset);
+    (words, errors)
+}
+
+/// Same as `tokenize_with`, for input that may be cut anywhere because more of it is yet to come.
+///
+/// Returns the words up to the last point where a word is sure to have ended, i.e. whitespace
+/// outside string literals and comments, and the byte offset of that point.
+pub fn tokenize_partial<'a>(
+    input: &'a str,
+    config: &LexerConfig,
+) -> Result<(Words<'a>, usize), LexError> {
+    let mut errors = vec![];
+    let (words, end) = scan(input, config, true, &mut errors);
+    let words = first_error(words, std::mem::take(&mut errors))?;
+    let words = check_words(words, config, &mut errors);
+    Ok((first_error(words, errors)?, end))
+}
+
+// words with their spans
+type Words<'a> = Vec<(&'a str, Span)>;
+
+// `words`, or the first of `errors` if there are any
+fn first_error<T>(words: T, errors: Vec<LexError>) -> Result<T, LexError> {
+    errors.into_iter().next().map_or(Ok(words), Err)
+}
+
+// Splits off the suffixes, and drops numbers and string literals that are malformed inside
+// with an error for each.
+fn check_words<'a>(
+    words: Vec<(&'a str, Span)>,
+    config: &LexerConfig,
+    errors: &mut Vec<LexError>,
+) -> Vec<(&'a str, Span)> {
+    words
         .into_iter()
-        .flat_map(|pre_token| split_off_reserved(&pre_token))
+        .flat_map(|(pre_token, span)| split_off_reserved(pre_token, span))
+        .filter(|&(word, span)| {
+            if is_number_literal(word) && Number::parse(word).is_none() {
+                errors.push(LexError {
+                    kind: LexErrorKind::NumberOutOfRange,
+                    span,
+                });
+                return false;
+            }
+            if word.starts_with(config.string_literal_open) {
+                if let Err(range) = unescape_with(word, config) {
+                    let start = span.start.advance(&word[..range.start]);
+                    errors.push(LexError {
+                        kind: LexErrorKind::InvalidEscape,
+                        span: Span::new(start, start.advance(&word[range])),
+                    });
+                    return false;
+                }
+            }
+            true
+        })
         .collect()
 }
 
-const RESERVED_ENDING: [&str; 6] = ["'d", "'c", "'st", "-il", "-o", "'i"];
+/// Decodes the value of a string literal, given with its `<` and `>`.
+///
+/// The escapes are `\<`, `\>`, `\\`, `\n`, `\t`, `\u{` 1 to 6 hex digits `}`, and `\` at the
+/// end of a line, which drops the line break and the indentation of the next line.
+/// On a bad escape, returns its byte range within `literal`.
+pub fn unescape(literal: &str) -> Result<Cow<'_, str>, Range<usize>> {
+    unescape_with(literal, &LexerConfig::default())
+}
 
-pub fn split_off_reserved(pre_token: &str) -> Vec<String> {
-    for ending in RESERVED_ENDING {
-        if let Some(remaining) = pre_token.strip_suffix(ending) {
-            if !remaining.is_empty() {
-                return vec![remaining.to_string(), ending.to_string()];
+/// Same as `unescape`, for the literal delimiters of `config`.
+pub fn unescape_with<'a>(
+    literal: &'a str,
+    config: &LexerConfig,
+) -> Result<Cow<'a, str>, Range<usize>> {
+    let (open, close) = (config.string_literal_open, config.string_literal_close);
+    let inner = literal
+        .strip_prefix(open)
+        .and_then(|rest| rest.strip_suffix(close))
+        .unwrap_or(literal);
+    if !inner.contains('\\') {
+        return Ok(Cow::Borrowed(inner));
+    }
+    // offset of `inner` within `literal`
+    let base = if literal.starts_with(open) {
+        open.len_utf8()
+    } else {
+        0
+    };
+    let mut value = String::new();
+    let mut chars = inner.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        let bad_escape = |end: usize| base + i..base + end;
+        let Some((j, escaped)) = chars.next() else {
+            return Err(bad_escape(inner.len()));
+        };
+        match escaped {
+            '\\' => value.push(escaped),
+            c if c == open || c == close => value.push(escaped),
+            'n' => value.push('\n'),
+            't' => value.push('\t'),
+            '\n' => while chars.next_if(|&(_, c)| c == ' ' || c == '\t').is_some() {},
+            'u' => {
+                let mut hex = String::new();
+                let mut end = j + 1;
+                if chars.next_if(|&(_, c)| c == '{').is_some() {
+                    end += 1;
+                    while let Some((k, c)) = chars.next_if(|&(_, c)| c != '}' && c != close) {
+                        hex.push(c);
+                        end = k + c.len_utf8();
+                    }
+                    match chars.next_if(|&(_, c)| c == '}') {
+                        Some((k, _)) => end = k + 1,
+                        None => return Err(bad_escape(end)),
+                    }
+                }
+                let decoded = (1..=6)
+                    .contains(&hex.len())
+                    .then(|| u32::from_str_radix(&hex, 16).ok())
+                    .flatten()
+                    .and_then(char::from_u32);
+                match decoded {
+                    Some(decoded) => value.push(decoded),
+                    None => return Err(bad_escape(end)),
+                }
             }
+            _ => return Err(bad_escape(j + escaped.len_utf8())),
         }
     }
-    vec![pre_token.to_string()]
+    Ok(Cow::Owned(value))
 }
 
-pub fn to_words(input: &str) -> Vec<String> {
+const RESERVED_ENDING: [&str; 6] = ["'d", "'c", "'st", "-il", "-o", "'i"];
+
+pub fn split_off_reserved(pre_token: &str, span: Span) -> impl Iterator<Item = (&str, Span)> {
+    let split = RESERVED_ENDING.iter().find_map(|&ending| {
+        let remaining = pre_token.strip_suffix(ending)?;
+        (!remaining.is_empty()).then(|| {
+            let (remaining_span, ending_span) = span.split_at(remaining);
+            ((remaining, remaining_span), (ending, ending_span))
+        })
+    });
+    match split {
+        Some((remaining, ending)) => std::iter::once(remaining).chain(Some(ending)),
+        None => std::iter::once((pre_token, span)).chain(None),
+    }
+}
+
+pub fn to_words<'a>(
+    input: &'a str,
+    config: &LexerConfig,
+) -> Result<Vec<(&'a str, Span)>, LexError> {
+    let mut errors = vec![];
+    let (words, _) = scan(input, config, false, &mut errors);
+    first_error(words, errors)
+}
+
+// A comment is `#` up to the end of the line, or `#{` up to the next `}#`.
+// Comments are skipped here; the CST picks them up again from the gaps between tokens.
+//
+// If `partial`, the end of `input` is not the end of the source: everything after the last
+// whitespace outside literals and comments is left for later, and its offset is returned.
+//
+// An error is pushed to `errors` and scanning goes on: an illegal character or a stray `>`
+// ends the word it is in and is skipped.
+#[allow(clippy::match_same_arms, clippy::too_many_lines)]
+fn scan<'a>(
+    input: &'a str,
+    config: &LexerConfig,
+    partial: bool,
+    errors: &mut Vec<LexError>,
+) -> (Words<'a>, usize) {
     #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
     enum State {
         ExpectingWordInitial,
         WordInternal,
         StringLiteralInternal,
+        // right after a `\\` in a string literal
+        StringLiteralEscape,
+        LineComment,
+        BlockComment,
     }
     let mut words = vec![];
     let mut state = State::ExpectingWordInitial;
 
-    let mut partial_word = String::new();
+    // a word is always a contiguous part of the input
+    let word =
+        |start: Position, end: Position| (&input[start.offset..end.offset], Span::new(start, end));
+    let mut word_start = Position::default();
+    let mut pos = Position::default();
+    // (number of words, offset) at the last point a word surely ended
+    let mut safe = (0, 0);
 
-    for c in input.chars() {
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
         use CharKind::{
-            EndsStringLiteral, SimplePunctuation, Space, StartsStringLiteral, WordConstituent,
+            EndsStringLiteral, SimplePunctuation, Space, StartsComment, StartsStringLiteral,
+            WordConstituent,
+        };
+        use State::{
+            BlockComment, ExpectingWordInitial, LineComment, StringLiteralEscape,
+            StringLiteralInternal, WordInternal,
         };
-        use State::{ExpectingWordInitial, StringLiteralInternal, WordInternal};
-        match (classify_char(c), state) {
-            (WordConstituent, ExpectingWordInitial) => {
-                partial_word.push(c);
+        let mut next_pos = pos.advance_char(c);
+        match (config.classify(c), state) {
+            (_, LineComment) => {
+                if c == '\n' {
+                    safe = (words.len(), next_pos.offset);
+                    state = ExpectingWordInitial;
+                }
+            }
+            (_, BlockComment) => {
+                if c == '}' && chars.next_if_eq(&'#').is_some() {
+                    next_pos = next_pos.advance_char('#');
+                    state = ExpectingWordInitial;
+                }
+            }
+            (Some(StartsComment), ExpectingWordInitial | WordInternal) => {
+                if state == WordInternal {
+                    words.push(word(word_start, pos));
+                }
+                if chars.next_if_eq(&'{').is_some() {
+                    next_pos = next_pos.advance_char('{');
+                    word_start = pos;
+                    state = BlockComment;
+                } else {
+                    state = LineComment;
+                }
+            }
+            (Some(WordConstituent), ExpectingWordInitial) => {
+                word_start = pos;
                 state = State::WordInternal;
             }
-            (WordConstituent, WordInternal) => {
-                partial_word.push(c);
+            (Some(WordConstituent), WordInternal) => { /* the word goes on */ }
+            (Some(Space), ExpectingWordInitial) => {
+                safe = (words.len(), next_pos.offset);
             }
-            (Space, ExpectingWordInitial) => { /* nothing is needed */ }
-            (Space, WordInternal) => {
-                words.push(partial_word.clone());
-                partial_word = String::new();
+            (Some(Space), WordInternal) => {
+                words.push(word(word_start, pos));
+                safe = (words.len(), next_pos.offset);
                 state = ExpectingWordInitial;
             }
-            (SimplePunctuation, ExpectingWordInitial) => {
-                words.push(c.to_string());
+            (Some(SimplePunctuation), ExpectingWordInitial) => {
+                words.push(word(pos, next_pos));
             }
-            (SimplePunctuation, WordInternal) => {
-                words.push(partial_word.clone());
-                words.push(c.to_string());
-                partial_word = String::new();
+            // the decimal point of a number, not the end of a sentence; it is `.` whatever the
+            // punctuation, as `Number` reads and writes it
+            (Some(SimplePunctuation) | None, WordInternal)
+                if c == '.'
+                    && is_number_literal(&input[word_start.offset..pos.offset])
+                    && !input[word_start.offset..pos.offset].contains('.')
+                    && chars.peek().is_some_and(char::is_ascii_digit) =>
+            { /* the word goes on */ }
+            (Some(SimplePunctuation), WordInternal) => {
+                words.push(word(word_start, pos));
+                words.push(word(pos, next_pos));
                 state = ExpectingWordInitial;
             }
-            (StartsStringLiteral, ExpectingWordInitial) => {
-                partial_word.push(c);
+            (Some(StartsStringLiteral), ExpectingWordInitial) => {
+                word_start = pos;
                 state = State::StringLiteralInternal;
             }
-            (StartsStringLiteral, WordInternal) => {
-                words.push(partial_word.clone());
-                partial_word = c.to_string();
+            (Some(StartsStringLiteral), WordInternal) => {
+                words.push(word(word_start, pos));
+                word_start = pos;
                 state = State::StringLiteralInternal;
             }
-            (EndsStringLiteral, StringLiteralInternal) => {
-                partial_word.push(c);
-                words.push(partial_word.clone());
-                partial_word = String::new();
+            (Some(EndsStringLiteral), StringLiteralInternal) => {
+                words.push(word(word_start, next_pos));
                 state = ExpectingWordInitial;
             }
             (_, StringLiteralInternal) => {
-                partial_word.push(c);
+                if c == '\\' {
+                    state = StringLiteralEscape;
+                }
             }
-            (EndsStringLiteral, _) => {
-                panic!("Unmatched > encountered");
+            // the escape itself is checked once the whole literal is known
+            (_, StringLiteralEscape) => {
+                state = StringLiteralInternal;
+            }
+            (None | Some(EndsStringLiteral), _) => {
+                if state == WordInternal {
+                    words.push(word(word_start, pos));
+                    state = ExpectingWordInitial;
+                }
+                errors.push(LexError {
+                    kind: if config.classify(c).is_none() {
+                        LexErrorKind::IllegalCharacter(c)
+                    } else {
+                        LexErrorKind::UnmatchedClosingBracket
+                    },
+                    span: Span::new(pos, next_pos),
+                });
             }
         }
+        pos = next_pos;
     }
-    words
+
+    if partial {
+        words.truncate(safe.0);
+        return (words, safe.1);
+    }
+    match state {
+        State::ExpectingWordInitial | State::LineComment => {}
+        State::WordInternal => words.push(word(word_start, pos)),
+        State::StringLiteralInternal | State::StringLiteralEscape => errors.push(LexError {
+            kind: LexErrorKind::UnterminatedStringLiteral,
+            span: Span::new(word_start, pos),
+        }),
+        State::BlockComment => errors.push(LexError {
+            kind: LexErrorKind::UnterminatedBlockComment,
+            span: Span::new(word_start, pos),
+        }),
+    }
+    (words, input.len())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{span::WithoutSpans, symbol::Symbol, token::TokenKind};
+
+    fn tokenize(input: &str) -> Result<Vec<(&str, Span)>, LexError> {
+        tokenize_with(input, &LexerConfig::default())
+    }
+
+    fn words(input: &str) -> Vec<String> {
+        tokenize(input)
+            .unwrap()
+            .into_iter()
+            .map(|(word, _)| word.to_string())
+            .collect()
+    }
+
+    fn ranges(input: &str) -> Vec<std::ops::Range<usize>> {
+        tokenize(input)
+            .unwrap()
+            .into_iter()
+            .map(|(_, span)| span.range())
+            .collect()
+    }
 
     #[test]
     fn simplest() {
-        assert_eq!(
-            tokenize("selsurle es iu."),
-            vec!["selsurle", "es", "iu", "."]
-        );
+        assert_eq!(words("selsurle es iu."), vec!["selsurle", "es", "iu", "."]);
     }
 
     #[test]
     fn number() {
         assert_eq!(
-            tokenize("laozia jerldir lerj 10 ad 10 el 168 ad 218."),
+            words("laozia jerldir lerj 10 ad 10 el 168 ad 218."),
             vec!["laozia", "jerldir", "lerj", "10", "ad", "10", "el", "168", "ad", "218", "."]
         );
     }
@@ -128,7 +524,7 @@ mod tests {
     #[test]
     fn case_ending() {
         assert_eq!(
-            tokenize("kernumesaxm'st sides-il io elx shrlo is selsurle iu'c."),
+            words("kernumesaxm'st sides-il io elx shrlo is selsurle iu'c."),
             vec![
                 "kernumesaxm",
                 "'st",
@@ -149,7 +545,7 @@ mod tests {
     #[test]
     fn space_string_literal_nospace() {
         assert_eq!(
-            tokenize("is jerldir'd xakant <selsurle>'c."),
+            words("is jerldir'd xakant <selsurle>'c."),
             vec!["is", "jerldir", "'d", "xakant", "<selsurle>", "'c", "."]
         );
     }
@@ -157,7 +553,7 @@ mod tests {
     #[test]
     fn nospace_string_literal_nospace() {
         assert_eq!(
-            tokenize("is jerldir'd xakant<selsurle>'c."),
+            words("is jerldir'd xakant<selsurle>'c."),
             vec!["is", "jerldir", "'d", "xakant", "<selsurle>", "'c", "."]
         );
     }
@@ -165,7 +561,7 @@ mod tests {
     #[test]
     fn nospace_string_literal_space() {
         assert_eq!(
-            tokenize("is jerldir'd xakant<selsurle> 'c."),
+            words("is jerldir'd xakant<selsurle> 'c."),
             vec!["is", "jerldir", "'d", "xakant", "<selsurle>", "'c", "."]
         );
     }
@@ -173,7 +569,7 @@ mod tests {
     #[test]
     fn space_string_literal_space() {
         assert_eq!(
-            tokenize("is jerldir'd xakant <selsurle> 'c."),
+            words("is jerldir'd xakant <selsurle> 'c."),
             vec!["is", "jerldir", "'d", "xakant", "<selsurle>", "'c", "."]
         );
     }
@@ -182,7 +578,7 @@ mod tests {
     #[allow(clippy::too_many_lines)]
     fn selsurle() {
         assert_eq!(
-            tokenize(include_str!("../selsurle.jmk4")),
+            words(include_str!("../selsurle.jmk4")),
             vec![
                 "lus",
                 "jmk4",
@@ -315,4 +711,286 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn spans() {
+        assert_eq!(
+            ranges("is jerldir'd xakant<selsurle> 'c."),
+            vec![0..2, 3..10, 10..12, 13..19, 19..29, 30..32, 32..33]
+        );
+    }
+
+    #[test]
+    fn line_and_column() {
+        let tokens = tokenize("selsurle es iu.\n\nkernumesaxm'st sides-il io").unwrap();
+        let (word, span) = &tokens[6];
+        assert_eq!(*word, "sides");
+        assert_eq!(
+            span.start,
+            Position {
+                offset: 32,
+                line: 3,
+                column: 16
+            }
+        );
+        assert_eq!(
+            span.end,
+            Position {
+                offset: 37,
+                line: 3,
+                column: 21
+            }
+        );
+    }
+
+    #[test]
+    fn string_literal_accepts_any_character() {
+        assert_eq!(
+            words("is jerldir'd xakant <Selsurle!>'c."),
+            vec!["is", "jerldir", "'d", "xakant", "<Selsurle!>", "'c", "."]
+        );
+    }
+
+    #[test]
+    fn illegal_character() {
+        let err = tokenize("selsurle es Iu.").unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::IllegalCharacter('I'));
+        assert_eq!(err.span.range(), 12..13);
+    }
+
+    #[test]
+    fn unmatched_closing_bracket() {
+        let err = tokenize("is jerldir'd xakant selsurle>'c.").unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::UnmatchedClosingBracket);
+        assert_eq!(err.span.range(), 28..29);
+    }
+
+    #[test]
+    fn unterminated_string_literal() {
+        let err = tokenize("is jerldir'd xakant <selsurle'c.").unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::UnterminatedStringLiteral);
+        assert_eq!(err.span.range(), 20..32);
+    }
+
+    #[test]
+    fn line_comment() {
+        assert_eq!(
+            words("# selsurle\nselsurle es iu. # iu\nselsurle es#iu\n iulo.#"),
+            vec!["selsurle", "es", "iu", ".", "selsurle", "es", "iulo", "."]
+        );
+        assert_eq!(ranges("selsurle#.\n."), vec![0..8, 11..12]);
+    }
+
+    #[test]
+    fn block_comment() {
+        assert_eq!(
+            words("selsurle #{ es\n iulo. }# es #{}# iu#{#}#."),
+            vec!["selsurle", "es", "iu", "."]
+        );
+        assert_eq!(ranges("#{ a }#iu."), vec![7..9, 9..10]);
+    }
+
+    #[test]
+    fn comment_character_in_string_literal() {
+        assert_eq!(
+            words("is selsurle <#{selsurle>'c."),
+            vec!["is", "selsurle", "<#{selsurle>", "'c", "."]
+        );
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        let err = tokenize("selsurle es iu. #{ iulo.\n").unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::UnterminatedBlockComment);
+        assert_eq!(err.span.range(), 16..25);
+    }
+
+    #[test]
+    fn decimal_point() {
+        assert_eq!(
+            words("laozia jerldir lerj -1.5 ad 10. 2.5'c 1.2.3"),
+            vec![
+                "laozia", "jerldir", "lerj", "-1.5", "ad", "10", ".", "2.5", "'c", "1.2", ".", "3"
+            ]
+        );
+        assert_eq!(words("jmk4.5"), vec!["jmk4", ".", "5"]);
+    }
+
+    #[test]
+    fn number_out_of_range() {
+        let err = tokenize("laozia jerldir lerj 99999999999999999999'c.").unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::NumberOutOfRange);
+        assert_eq!(err.span.range(), 20..40);
+    }
+
+    #[test]
+    fn escaped_closing_bracket() {
+        assert_eq!(
+            words(r"is selsurle <a\>b\\>'c."),
+            vec!["is", "selsurle", r"<a\>b\\>", "'c", "."]
+        );
+        let err = tokenize(r"is selsurle <a\>'c.").unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::UnterminatedStringLiteral);
+    }
+
+    #[test]
+    fn unescape_literal() {
+        assert_eq!(unescape("<selsurle>").unwrap(), "selsurle");
+        assert_eq!(
+            unescape(r"<\<a\> \\ \n\t\u{41}\u{1F600}>").unwrap(),
+            "<a> \\ \n\tA\u{1F600}"
+        );
+        assert_eq!(
+            unescape("<jerldir \\\n    xakant>").unwrap(),
+            "jerldir xakant"
+        );
+        assert_eq!(unescape("<jerldir\nxakant>").unwrap(), "jerldir\nxakant");
+        assert_eq!(unescape(r"<a\qb>"), Err(2..4));
+        assert_eq!(unescape(r"<a\u{110000}>"), Err(2..12));
+        assert_eq!(unescape(r"<a\u{}>"), Err(2..6));
+        assert_eq!(unescape(r"<a\u41>"), Err(2..4));
+        assert_eq!(unescape(r"<a\u{41>"), Err(2..7));
+    }
+
+    #[test]
+    fn invalid_escape() {
+        let err = tokenize("is selsurle\n<jerldir \\x>'c.").unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::InvalidEscape);
+        assert_eq!(err.span.range(), 21..23);
+        assert_eq!((err.span.start.line, err.span.start.column), (2, 10));
+    }
+
+    fn words_with(input: &str, config: &LexerConfig) -> Vec<String> {
+        tokenize_with(input, config)
+            .unwrap()
+            .into_iter()
+            .map(|(word, _)| word.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn unicode_xid() {
+        let config = LexerConfig::unicode_xid();
+        assert_eq!(
+            words_with("Selsurle es 管理推進基盤'd ünïcode.", &config),
+            vec!["Selsurle", "es", "管理推進基盤", "'d", "ünïcode", "."]
+        );
+        assert_eq!(
+            tokenize_with("selsurle es iu!", &config).unwrap_err().kind,
+            LexErrorKind::IllegalCharacter('!')
+        );
+        assert_eq!(
+            tokenize("selsurle es 管理.").unwrap_err().kind,
+            LexErrorKind::IllegalCharacter('管')
+        );
+    }
+
+    #[test]
+    fn custom_config() {
+        let config = LexerConfig {
+            alphabet: Alphabet::Chars(vec!['a', 'b', 'd', '\'']),
+            period: '!',
+            comma: '.',
+            colon: ';',
+            string_literal_open: '«',
+            string_literal_close: '»',
+        };
+        assert_eq!(
+            words_with("ab'd «a<b>\\»c» ba!.", &config),
+            vec!["ab", "'d", "«a<b>\\»c»", "ba", "!", "."]
+        );
+        assert_eq!(unescape_with("«a<b>\\»c»", &config).unwrap(), "a<b>»c");
+        assert_eq!(
+            tokenize_with("ab, ba.", &config).unwrap_err().kind,
+            LexErrorKind::IllegalCharacter(',')
+        );
+        assert_eq!(
+            crate::token::tokenize_with("ba!.;", &config)
+                .unwrap()
+                .iter()
+                .map(|token| token.kind.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                TokenKind::NormalIdent {
+                    ident: Symbol::from("ba")
+                },
+                TokenKind::Reserved(Reserved::PunctuationPeriod),
+                TokenKind::Reserved(Reserved::PunctuationComma),
+                TokenKind::Reserved(Reserved::PunctuationColon),
+            ]
+        );
+    }
+
+    #[test]
+    fn native_punctuation() {
+        let config = LexerConfig {
+            period: '。',
+            comma: '、',
+            colon: '：',
+            ..LexerConfig::unicode_xid()
+        };
+        let sentences = crate::parse_program_with_config(
+            "lus jmk4'd jerldir adit kernumesaxm、deln。\n\
+             nert ad ektir'st es_tydivexy-o： ektir mol cecioj 4 ad 204。",
+            &config,
+        )
+        .unwrap();
+        assert_eq!(
+            crate::parse_program_with_config("c es 1.5。", &config)
+                .unwrap()
+                .without_spans(),
+            crate::parse_program("c es 1.5.").unwrap().without_spans()
+        );
+        assert_eq!(
+            sentences.without_spans(),
+            crate::parse_program(
+                "lus jmk4'd jerldir adit kernumesaxm, deln.\n\
+                 nert ad ektir'st es_tydivexy-o : ektir mol cecioj 4 ad 204."
+            )
+            .unwrap()
+            .without_spans()
+        );
+    }
+
+    #[test]
+    fn recovery() {
+        let (words, errors) =
+            tokenize_with_recovery("selsurle $es iu> <a\\q> 1.", &LexerConfig::default());
+        assert_eq!(
+            words.iter().map(|(w, _)| *w).collect::<Vec<_>>(),
+            vec!["selsurle", "es", "iu", "1", "."]
+        );
+        assert_eq!(
+            errors
+                .into_iter()
+                .map(|err| (err.kind, err.span.range()))
+                .collect::<Vec<_>>(),
+            vec![
+                (LexErrorKind::IllegalCharacter('$'), 9..10),
+                (LexErrorKind::UnmatchedClosingBracket, 15..16),
+                (LexErrorKind::InvalidEscape, 19..21),
+            ]
+        );
+    }
+
+    #[test]
+    fn partial() {
+        let config = LexerConfig::default();
+        let (words, end) = tokenize_partial("selsurle es 1", &config).unwrap();
+        assert_eq!(
+            words.iter().map(|(w, _)| *w).collect::<Vec<_>>(),
+            vec!["selsurle", "es"]
+        );
+        assert_eq!(end, 12);
+        let (words, end) = tokenize_partial("is selsurle <iu 'c", &config).unwrap();
+        assert_eq!(words.len(), 2);
+        assert_eq!(end, 12);
+        let (words, end) = tokenize_partial("iu. # comment\n #{ a\n", &config).unwrap();
+        assert_eq!(
+            words.iter().map(|(w, _)| *w).collect::<Vec<_>>(),
+            vec!["iu", "."]
+        );
+        assert_eq!(end, 15);
+        assert!(tokenize_partial("selsurle I", &config).is_err());
+    }
 }
@@ -0,0 +1,216 @@
+//! Transliteration of other scripts into the romanisation the lexer reads.
+//!
+//! The native script has no Unicode encoding of its own, so its table is supplied by the
+//! user (e.g. for the private use area code points of a font) with `Transliteration::from_table`.
+//! String literals and comments are left as they are.
+
+use crate::{
+    span::{Position, Span},
+    tokenize::LexerConfig,
+};
+
+/// A table of replacements, applied longest match first.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Transliteration {
+    rules: Vec<(String, String)>,
+}
+
+impl Transliteration {
+    #[must_use]
+    pub fn new<S: Into<String>, T: Into<String>>(rules: impl IntoIterator<Item = (S, T)>) -> Self {
+        let mut rules = rules
+            .into_iter()
+            .map(|(from, to)| (from.into(), to.into()))
+            .filter(|(from, _)| !from.is_empty())
+            .collect::<Vec<_>>();
+        rules.sort_by_key(|(from, _)| std::cmp::Reverse(from.len()));
+        Self { rules }
+    }
+
+    /// Reads a table with one rule per line, the original and its romanisation separated by
+    /// whitespace. Empty lines and lines starting with `#` are ignored.
+    #[must_use]
+    pub fn from_table(table: &str) -> Self {
+        Self::new(table.lines().filter_map(|line| {
+            let line = line.trim();
+            if line.starts_with('#') {
+                return None;
+            }
+            line.split_once(char::is_whitespace)
+                .map(|(from, to)| (from.to_string(), to.trim_start().to_string()))
+        }))
+    }
+
+    /// An ASCII fallback for the three non-Latin letters, after their X-SAMPA symbols:
+    /// `p\` for `φ`, `B` for `β` and `Z` for `ж`.
+    #[must_use]
+    pub fn ascii() -> Self {
+        Self::new([("p\\", "φ"), ("B", "β"), ("Z", "ж")])
+    }
+
+    /// Rewrites `input` into the romanisation, keeping track of where each part came from.
+    #[must_use]
+    pub fn apply(&self, input: &str, config: &LexerConfig) -> Transliterated {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        enum State {
+            Code,
+            StringLiteral,
+            StringLiteralEscape,
+            LineComment,
+            BlockComment,
+        }
+        let mut text = String::new();
+        let mut offsets = vec![(0, 0)];
+        let mut state = State::Code;
+        let mut i = 0;
+        while i < input.len() {
+            let rest = &input[i..];
+            let rule = (state == State::Code)
+                .then(|| {
+                    self.rules
+                        .iter()
+                        .find(|(from, _)| rest.starts_with(from.as_str()))
+                })
+                .flatten();
+            if let Some((from, to)) = rule {
+                text.push_str(to);
+                i += from.len();
+                offsets.push((text.len(), i));
+                continue;
+            }
+            let c = rest.chars().next().unwrap_or_default();
+            let (next_state, len) = match state {
+                State::Code if c == config.string_literal_open => {
+                    (State::StringLiteral, c.len_utf8())
+                }
+                State::Code if rest.starts_with("#{") => (State::BlockComment, 2),
+                State::Code if c == '#' => (State::LineComment, 1),
+                State::StringLiteral if c == '\\' => (State::StringLiteralEscape, 1),
+                State::StringLiteral if c == config.string_literal_close => {
+                    (State::Code, c.len_utf8())
+                }
+                State::StringLiteralEscape => (State::StringLiteral, c.len_utf8()),
+                State::LineComment if c == '\n' => (State::Code, 1),
+                State::BlockComment if rest.starts_with("}#") => (State::Code, 2),
+                state => (state, c.len_utf8()),
+            };
+            text.push_str(&rest[..len]);
+            i += len;
+            offsets.push((text.len(), i));
+            state = next_state;
+        }
+        Transliterated {
+            text,
+            offsets,
+            line_starts: std::iter::once(0)
+                .chain(input.match_indices('\n').map(|(i, _)| i + 1))
+                .collect(),
+            original: input.to_string(),
+        }
+    }
+}
+
+/// The romanised text together with a map back to the original.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Transliterated {
+    pub text: String,
+    // (offset in `text`, offset in `original`) at every boundary between replacements
+    offsets: Vec<(usize, usize)>,
+    line_starts: Vec<usize>,
+    original: String,
+}
+
+impl Transliterated {
+    /// The span in the original text that `span` of the romanised text came from.
+    /// A span starting or ending in the middle of a replacement is widened to cover it.
+    #[must_use]
+    pub fn original_span(&self, span: Span) -> Span {
+        Span::new(
+            self.position(self.original_offset(span.start.offset, false)),
+            self.position(self.original_offset(span.end.offset, true)),
+        )
+    }
+
+    fn original_offset(&self, offset: usize, round_up: bool) -> usize {
+        let i = self.offsets.partition_point(|&(text, _)| text <= offset);
+        let (text, original) = self.offsets[i - 1];
+        if text == offset || !round_up {
+            original
+        } else {
+            self.offsets
+                .get(i)
+                .map_or(self.original.len(), |&(_, original)| original)
+        }
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        Position {
+            offset,
+            line,
+            column: self.original[line_start..offset].chars().count() + 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        symbol::Symbol,
+        token::{tokenize_transliterated, TokenKind},
+    };
+
+    #[test]
+    fn ascii() {
+        let config = LexerConfig::default();
+        let transliterated =
+            Transliteration::ascii().apply("Bap\\ <BZ\\>> Zi #B\n#{Z}#Z.", &config);
+        assert_eq!(transliterated.text, "βaφ <BZ\\>> жi #B\n#{Z}#ж.");
+    }
+
+    #[test]
+    fn spans_point_at_the_original() {
+        let source = "selsurle es\nBap\\'d iu.";
+        let tokens =
+            tokenize_transliterated(source, &Transliteration::ascii(), &LexerConfig::default())
+                .unwrap();
+        assert_eq!(
+            tokens[2].kind,
+            TokenKind::NormalIdent {
+                ident: Symbol::from("βaφ")
+            }
+        );
+        assert_eq!(tokens[2].span.range(), 12..16);
+        assert_eq!(&source[tokens[3].span.range()], "'d");
+        assert_eq!(
+            (tokens[3].span.start.line, tokens[3].span.start.column),
+            (2, 5)
+        );
+    }
+
+    #[test]
+    fn table() {
+        let transliteration = Transliteration::from_table(
+            "# native script\n\u{E000} sel\n\u{E001}\u{E002} surle\n\u{E001} s\n\n\u{E003}\t'd\n",
+        );
+        let source = "\u{E000}\u{E001}\u{E002}\u{E003} es \u{E001}iu.";
+        let transliterated = transliteration.apply(source, &LexerConfig::default());
+        assert_eq!(transliterated.text, "selsurle'd es siu.");
+        let tokens =
+            tokenize_transliterated(source, &transliteration, &LexerConfig::default()).unwrap();
+        assert_eq!(&source[tokens[0].span.range()], "\u{E000}\u{E001}\u{E002}");
+        assert_eq!(&source[tokens[1].span.range()], "\u{E003}");
+        assert_eq!(tokens[2].span.start.column, 6);
+    }
+
+    #[test]
+    fn error_spans_point_at_the_original() {
+        let source = "Bap\\ es Iu.";
+        let err =
+            tokenize_transliterated(source, &Transliteration::ascii(), &LexerConfig::default())
+                .unwrap_err();
+        assert_eq!(err.span.range(), 8..9);
+    }
+}
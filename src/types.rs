@@ -0,0 +1,543 @@
+//! Static types of nouns.
+//!
+//! Literals have the type of their kind, names the type of what they resolve to, and
+//! `a'd b` the type of the property `b` of the type of `a`. The types of the names and
+//! properties the runtime provides are written as a table:
+//!
+//! ```text
+//! jerldir          window
+//! window'd xakant  string
+//! ```
+//!
+//! Predicate parameters and names imported from other files have no known type and match
+//! any type.
+
+use std::{collections::HashMap, fmt, iter};
+
+use crate::{
+    loader::BUILTIN_MODULE,
+    parser::{Cond, Noun, NounId, PrimaryNoun, Procedure, Sentence},
+    resolve::{Definition, DefinitionKind, Resolution},
+    signature::SignatureRegistry,
+    span::Span,
+    token::Reserved,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Type {
+    Window,
+    Widget,
+    Number,
+    String,
+    Boolean,
+}
+
+impl Type {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Window => "window",
+            Self::Widget => "widget",
+            Self::Number => "number",
+            Self::String => "string",
+            Self::Boolean => "boolean",
+        }
+    }
+
+    #[must_use]
+    pub fn parse(name: &str) -> Option<Self> {
+        [
+            Self::Window,
+            Self::Widget,
+            Self::Number,
+            Self::String,
+            Self::Boolean,
+        ]
+        .into_iter()
+        .find(|ty| ty.as_str() == name)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The names and properties of the `jmk4` module.
+pub const JMK4_TYPES: &str = "# name or property            type
+jerldir                         window
+kernumesaxm                     widget
+deln                            boolean
+iu                              boolean
+iulo                            boolean
+window'd xakant                 string
+widget'd snelyo                 number
+widget'd pestavilersnelyo       number
+";
+
+/// The types of the names and properties the runtime provides.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeTable {
+    names: HashMap<String, Type>,
+    properties: HashMap<(Type, String), Type>,
+}
+
+impl TypeTable {
+    /// Reads a table with one name, or one property `type'd name`, per line followed by its
+    /// type. Empty lines and lines starting with `#` are ignored.
+    ///
+    /// # Errors
+    /// Returns the (1-based) number of the first line that cannot be read.
+    pub fn from_table(table: &str) -> Result<Self, usize> {
+        let mut types = Self::default();
+        for (i, line) in table.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let owner =
+                |word: &str| Type::parse(word.strip_suffix(Reserved::ApostropheD.as_str())?);
+            match words[..] {
+                [name, ty] => {
+                    let ty = Type::parse(ty).ok_or(i + 1)?;
+                    types.names.insert(name.to_string(), ty);
+                }
+                [owner_word, name, ty] => {
+                    let owner = owner(owner_word).ok_or(i + 1)?;
+                    let ty = Type::parse(ty).ok_or(i + 1)?;
+                    types.properties.insert((owner, name.to_string()), ty);
+                }
+                _ => return Err(i + 1),
+            }
+        }
+        Ok(types)
+    }
+
+    /// The names and properties of the `jmk4` module, from `JMK4_TYPES`.
+    #[must_use]
+    pub fn jmk4() -> Self {
+        Self::from_table(JMK4_TYPES).expect("JMK4_TYPES is valid")
+    }
+
+    #[must_use]
+    pub fn name(&self, name: &str) -> Option<Type> {
+        self.names.get(name).copied()
+    }
+
+    #[must_use]
+    pub fn property(&self, owner: Type, name: &str) -> Option<Type> {
+        self.properties.get(&(owner, name.to_string())).copied()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeErrorKind {
+    Mismatch { expected: Type, found: Type },
+    // `name` is spelled as in the source
+    UnknownProperty { owner: Type, name: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TypeError {
+    pub kind: TypeErrorKind,
+    pub span: Span,
+}
+
+impl TypeError {
+    /// A stable identifier of the kind of error, independent of the message wording.
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self.kind {
+            TypeErrorKind::Mismatch { .. } => "E0501",
+            TypeErrorKind::UnknownProperty { .. } => "E0502",
+        }
+    }
+}
+
+/// The inferred type of each noun.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Types {
+    nouns: HashMap<NounId, Type>,
+}
+
+impl Types {
+    /// The type of `noun`, if known.
+    #[must_use]
+    pub fn type_of(&self, noun: &Noun) -> Option<Type> {
+        self.nouns.get(&noun.id).copied()
+    }
+}
+
+/// Infers the type of every noun of `sentences`, whose names `resolution` resolves, and
+/// checks them against the verb signatures and the types of variables and properties.
+#[must_use]
+pub fn check_types(
+    sentences: &[Sentence],
+    resolution: &Resolution,
+    signatures: &SignatureRegistry,
+    table: &TypeTable,
+) -> (Types, Vec<TypeError>) {
+    let mut checker = Checker {
+        resolution,
+        signatures,
+        table,
+        variables: HashMap::new(),
+        types: Types::default(),
+        errors: vec![],
+    };
+
+    // A variable has the type of its initializer, which may use variables declared later
+    loop {
+        let mut changed = false;
+        for sentence in sentences {
+            let Sentence::VarDecl(noun1, noun2) = sentence else {
+                continue;
+            };
+            let Some(key) = checker.declared_variable(noun1) else {
+                continue;
+            };
+            if !checker.variables.contains_key(&key) {
+                if let Some(ty) = checker.noun(noun2) {
+                    checker.variables.insert(key, ty);
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    checker.types = Types::default();
+    checker.errors.clear();
+
+    for sentence in sentences {
+        checker.sentence(sentence);
+    }
+    (checker.types, checker.errors)
+}
+
+struct Checker<'a> {
+    resolution: &'a Resolution,
+    signatures: &'a SignatureRegistry,
+    table: &'a TypeTable,
+    // keyed by the declaring noun
+    variables: HashMap<NounId, Type>,
+    types: Types,
+    errors: Vec<TypeError>,
+}
+
+impl Checker<'_> {
+    // The key of the variable `noun` declares, if it is a declaration
+    fn declared_variable(&self, noun: &Noun) -> Option<NounId> {
+        let definition = self.resolution.definition_of(noun)?;
+        (definition.kind == DefinitionKind::Variable && definition.noun == Some(noun.id))
+            .then_some(noun.id)
+    }
+
+    fn definition(&self, definition: &Definition) -> Option<Type> {
+        match &definition.kind {
+            DefinitionKind::Import { module_path } if module_path == &[BUILTIN_MODULE] => {
+                self.table.name(&definition.name)
+            }
+            DefinitionKind::Builtin => self.table.name(&definition.name),
+            DefinitionKind::Variable => definition
+                .noun
+                .and_then(|noun| self.variables.get(&noun).copied()),
+            DefinitionKind::Import { .. } | DefinitionKind::Parameter => None,
+        }
+    }
+
+    fn noun(&mut self, noun: &Noun) -> Option<Type> {
+        let mut primaries = noun.modifier.iter().chain(iter::once(&noun.head));
+        let mut ty = match primaries.next()? {
+            PrimaryNoun::StringLiteral { .. } => Some(Type::String),
+            PrimaryNoun::Number { .. } => Some(Type::Number),
+            PrimaryNoun::Ident { .. } => self
+                .resolution
+                .definition_of(noun)
+                .and_then(|definition| self.definition(definition)),
+        };
+        for primary in primaries {
+            let Some(owner) = ty else {
+                break;
+            };
+            let name = primary.to_string();
+            ty = self.table.property(owner, &name);
+            if ty.is_none() {
+                self.errors.push(TypeError {
+                    kind: TypeErrorKind::UnknownProperty { owner, name },
+                    span: primary.span(),
+                });
+            }
+        }
+        if let Some(ty) = ty {
+            self.types.nouns.insert(noun.id, ty);
+        }
+        ty
+    }
+
+    fn expect(&mut self, noun: &Noun, expected: Option<Type>) {
+        let found = self.noun(noun);
+        if let (Some(expected), Some(found)) = (expected, found) {
+            if expected != found {
+                self.errors.push(TypeError {
+                    kind: TypeErrorKind::Mismatch { expected, found },
+                    span: noun.span,
+                });
+            }
+        }
+    }
+
+    fn sentence(&mut self, sentence: &Sentence) {
+        match sentence {
+            Sentence::Procedure(procedure) => self.procedure(procedure),
+            Sentence::Import(_) => {}
+            // for a declaration, `noun1` has the type of `noun2` already
+            Sentence::VarDecl(noun1, noun2) => {
+                let ty = self.noun(noun1);
+                self.expect(noun2, ty);
+            }
+            Sentence::PredicateDecl {
+                noun_list, cond, ..
+            } => {
+                for noun in noun_list {
+                    self.noun(noun);
+                }
+                self.cond(cond);
+            }
+            Sentence::EventHandler {
+                event_conds,
+                procedures,
+                ..
+            } => {
+                for event_cond in event_conds {
+                    self.noun(&event_cond.noun);
+                }
+                for procedure in procedures {
+                    self.procedure(procedure);
+                }
+            }
+            Sentence::GuardedEventHandler {
+                event_conds,
+                guard,
+                procedures,
+                ..
+            } => {
+                for event_cond in event_conds {
+                    self.noun(&event_cond.noun);
+                }
+                self.cond(guard);
+                for procedure in procedures {
+                    self.procedure(procedure);
+                }
+            }
+        }
+    }
+
+    fn cond(&mut self, cond: &Cond) {
+        for elem in &cond.0 {
+            self.noun(&elem.noun);
+            for nouns_with_case in &elem.nouns_with_case_array {
+                for noun in &nouns_with_case.nouns {
+                    self.noun(noun);
+                }
+            }
+        }
+    }
+
+    fn procedure(&mut self, procedure: &Procedure) {
+        let signature = self.signatures.get(&procedure.verb.0);
+        self.expect(&procedure.noun, signature.and_then(|s| s.object));
+        for nouns_with_case in &procedure.nouns_with_case_array {
+            let ty = signature
+                .and_then(|s| s.case(nouns_with_case.case))
+                .and_then(|case| case.ty);
+            for noun in &nouns_with_case.nouns {
+                self.expect(noun, ty);
+            }
+        }
+        for mea_elem in procedure.mea_clause.iter().flatten() {
+            let name = mea_elem.named_parameter.to_string();
+            let ty = signature
+                .and_then(|s| s.named_parameter(&name))
+                .and_then(|parameter| parameter.ty);
+            for noun in &mea_elem.noun_list {
+                self.expect(noun, ty);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use super::*;
+    use crate::{parse_program, resolve::resolve};
+
+    fn check(source: &str) -> (Vec<Sentence>, Types, Vec<TypeError>) {
+        let sentences = parse_program(source).unwrap();
+        let (resolution, _) = resolve(&sentences);
+        let (types, errors) = check_types(
+            &sentences,
+            &resolution,
+            &SignatureRegistry::jmk4(),
+            &TypeTable::jmk4(),
+        );
+        (sentences, types, errors)
+    }
+
+    fn errors(source: &str) -> Vec<(TypeErrorKind, Range<usize>)> {
+        check(source)
+            .2
+            .into_iter()
+            .map(|err| (err.kind, err.span.range()))
+            .collect()
+    }
+
+    #[test]
+    fn selsurle() {
+        let (sentences, types, errors) = check(include_str!("../selsurle.jmk4"));
+        assert_eq!(errors, vec![]);
+        // lexisnain jerldir fal kernumesaxm'd snelyo mea xerfo es 1 ad 1 mal dejix es deln
+        let Sentence::EventHandler { procedures, .. } = &sentences[4] else {
+            panic!("{:?}", sentences[4]);
+        };
+        let procedure = &procedures[0];
+        assert_eq!(types.type_of(&procedure.noun), Some(Type::Window));
+        assert_eq!(
+            types.type_of(&procedure.nouns_with_case_array[0].nouns[0]),
+            Some(Type::Number)
+        );
+        let mea_clause = procedure.mea_clause.as_ref().unwrap();
+        assert_eq!(
+            types.type_of(&mea_clause[1].noun_list[0]),
+            Some(Type::Boolean)
+        );
+    }
+
+    #[test]
+    fn literals_and_variables() {
+        let (sentences, types, errors) = check("a es b. b es <selsurle>. c es -1.5. d es nert.");
+        assert_eq!(errors, vec![]);
+        let types = sentences
+            .iter()
+            .map(|sentence| match sentence {
+                Sentence::VarDecl(noun, _) => types.type_of(noun),
+                _ => panic!("{sentence:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                Some(Type::String),
+                Some(Type::String),
+                Some(Type::Number),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn same_range_in_another_program() {
+        let (sentences, types, _) = check("a es <b>.");
+        let other = parse_program("a es 100.").unwrap();
+        let (Sentence::VarDecl(_, string), Sentence::VarDecl(_, number)) =
+            (&sentences[0], &other[0])
+        else {
+            panic!("{sentences:?}");
+        };
+        assert_eq!(string.span, number.span);
+        assert_eq!(types.type_of(string), Some(Type::String));
+        assert_eq!(types.type_of(number), None);
+    }
+
+    #[test]
+    fn mismatch() {
+        assert_eq!(
+            errors("lus jmk4'd jerldir. laozia jerldir lerj <10> ad 10 el 168 ad 218."),
+            vec![(
+                TypeErrorKind::Mismatch {
+                    expected: Type::Number,
+                    found: Type::String
+                },
+                40..44
+            )]
+        );
+        assert_eq!(
+            errors("lus jmk4'd jerldir ad kernumesaxm. selsurle es 1. xes kernumesaxm lerj 1 el 2 mea dejix es selsurle."),
+            vec![
+                (
+                    TypeErrorKind::Mismatch {
+                        expected: Type::Window,
+                        found: Type::Widget
+                    },
+                    54..65
+                ),
+                (
+                    TypeErrorKind::Mismatch {
+                        expected: Type::Boolean,
+                        found: Type::Number
+                    },
+                    91..99
+                ),
+            ]
+        );
+        assert_eq!(
+            errors("lus jmk4'd jerldir. jerldir'd xakant es 1."),
+            vec![(
+                TypeErrorKind::Mismatch {
+                    expected: Type::String,
+                    found: Type::Number
+                },
+                40..41
+            )]
+        );
+    }
+
+    #[test]
+    fn properties() {
+        let (sentences, types, errors) =
+            check("lus jmk4'd kernumesaxm. is kernumesaxm'd snelyo'd xakant 1'c. is kernumesaxm'd xakant 1'c.");
+        let errors = errors
+            .into_iter()
+            .map(|err| (err.kind, err.span.range()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    TypeErrorKind::UnknownProperty {
+                        owner: Type::Number,
+                        name: "xakant".to_string()
+                    },
+                    50..56
+                ),
+                (
+                    TypeErrorKind::UnknownProperty {
+                        owner: Type::Widget,
+                        name: "xakant".to_string()
+                    },
+                    79..85
+                ),
+            ]
+        );
+        let Sentence::Procedure(procedure) = &sentences[1] else {
+            panic!("{:?}", sentences[1]);
+        };
+        assert_eq!(types.type_of(&procedure.noun), None);
+    }
+
+    #[test]
+    fn table() {
+        let table =
+            TypeTable::from_table("# names\nselsurle boolean\n\nwidget'd snelyo number\n").unwrap();
+        assert_eq!(table.name("selsurle"), Some(Type::Boolean));
+        assert_eq!(table.property(Type::Widget, "snelyo"), Some(Type::Number));
+        assert_eq!(table.property(Type::Window, "snelyo"), None);
+        assert_eq!(TypeTable::from_table("a boolean\nb colour\n"), Err(2));
+        assert_eq!(TypeTable::from_table("colour'd a number\n"), Err(1));
+    }
+}